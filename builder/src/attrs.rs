@@ -0,0 +1,221 @@
+use quote::ToTokens;
+use syn::{meta::ParseNestedMeta, token, Error, LitBool, LitStr, Path, Result, Token};
+
+/// The options of one attribute, `builder(...)` or the nested `setter(...)`.
+///
+/// Every option is looked up through `key`, which rejects unknown and repeated
+/// ones, and its value is read with the parser for its kind, which names the
+/// expected form when it is given something else.
+pub struct Options {
+    attr: &'static str,
+    keys: &'static [&'static str],
+    /// Options of the same attribute that belong somewhere else, and where.
+    misplaced: &'static [&'static str],
+    place: &'static str,
+    seen: Vec<Path>,
+}
+
+impl Options {
+    pub fn new(attr: &'static str, keys: &'static [&'static str]) -> Self {
+        Options {
+            attr,
+            keys,
+            misplaced: &[],
+            place: "",
+            seen: vec![],
+        }
+    }
+
+    pub fn misplaced(mut self, keys: &'static [&'static str], place: &'static str) -> Self {
+        self.misplaced = keys;
+        self.place = place;
+        self
+    }
+
+    /// The name of the option `meta` is about, once it is known to be valid.
+    pub fn key(&mut self, meta: &ParseNestedMeta) -> Result<&'static str> {
+        let name = meta
+            .path
+            .get_ident()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| meta.path.to_token_stream().to_string().replace(' ', ""));
+        let key = match self.keys.iter().find(|key| **key == name) {
+            Some(key) => *key,
+            None if self.misplaced.contains(&name.as_str()) => {
+                return Err(meta.error(format!(
+                    "`{}({})` is only supported {}",
+                    self.attr, name, self.place
+                )));
+            }
+            None => {
+                return Err(meta.error(match suggestion(&name, self.keys) {
+                    Some(key) => format!(
+                        "unknown option `{}` in `{}(...)`, did you mean `{}`?",
+                        name, self.attr, key
+                    ),
+                    None => format!(
+                        "unknown option `{}` in `{}(...)`, expected one of {}",
+                        name,
+                        self.attr,
+                        self.keys
+                            .iter()
+                            .map(|key| format!("`{}`", key))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }));
+            }
+        };
+
+        // Lists may be split up, their own options are checked one by one.
+        if self.seen(key).is_some() && !meta.input.peek(token::Paren) {
+            return Err(meta.error(format!("duplicate `{}({})`", self.attr, key)));
+        }
+        self.seen.push(meta.path.clone());
+        Ok(key)
+    }
+
+    /// Where the option `key` was given, if it was.
+    pub fn seen(&self, key: &str) -> Option<&Path> {
+        self.seen.iter().find(|path| path.is_ident(key))
+    }
+
+    /// Fails if both `a` and `b` were given, pointing at the one given last.
+    pub fn conflict(&self, a: &str, b: &str) -> Result<()> {
+        let position = |key| self.seen.iter().position(|path| path.is_ident(key));
+        let (a, b) = match (position(a), position(b)) {
+            (Some(a), Some(b)) if b < a => (b, a),
+            (Some(a), Some(b)) => (a, b),
+            _ => return Ok(()),
+        };
+        conflict(
+            Some((self.attr, &self.seen[a])),
+            Some((self.attr, &self.seen[b])),
+        )
+    }
+
+    /// An option without a value, like `skip`.
+    pub fn flag(&self, meta: &ParseNestedMeta) -> Result<()> {
+        if meta.input.peek(Token![=]) || meta.input.peek(token::Paren) {
+            let key = meta.path.get_ident().unwrap();
+            return Err(meta.error(format!("`{}({})` takes no value", self.attr, key)));
+        }
+        Ok(())
+    }
+
+    /// An option that is either bare, meaning `true`, or `= true`/`= false`.
+    pub fn bool(&self, meta: &ParseNestedMeta) -> Result<bool> {
+        if !meta.input.peek(Token![=]) {
+            self.flag(meta)?;
+            return Ok(true);
+        }
+
+        let key = meta.path.get_ident().unwrap();
+        let expected = || {
+            format!(
+                "expected `{}({})` or `{}({} = false)`",
+                self.attr, key, self.attr, key
+            )
+        };
+        meta.value()?
+            .parse::<LitBool>()
+            .map(|lit| lit.value)
+            .map_err(|err| Error::new(err.span(), expected()))
+    }
+
+    /// An option taking a string, like `each = "arg"`.
+    pub fn string(&self, meta: &ParseNestedMeta) -> Result<LitStr> {
+        let key = meta.path.get_ident().unwrap();
+        let expected = || format!("expected `{}({} = \"...\")`", self.attr, key);
+        if !meta.input.peek(Token![=]) {
+            return Err(meta.error(expected()));
+        }
+        meta.value()?
+            .parse::<LitStr>()
+            .map_err(|err| Error::new(err.span(), expected()))
+    }
+
+    /// An option that is either bare or takes a string, like `default`.
+    pub fn optional_string(&self, meta: &ParseNestedMeta) -> Result<Option<LitStr>> {
+        let key = meta.path.get_ident().unwrap();
+        let expected = || {
+            format!(
+                "expected `{}({})` or `{}({} = \"...\")`",
+                self.attr, key, self.attr, key
+            )
+        };
+        if meta.input.peek(token::Paren) {
+            return Err(meta.error(expected()));
+        }
+        if !meta.input.peek(Token![=]) {
+            return Ok(None);
+        }
+        meta.value()?
+            .parse::<LitStr>()
+            .map(Some)
+            .map_err(|err| Error::new(err.span(), expected()))
+    }
+
+    /// An option with options of its own, like `setter(...)`.
+    pub fn list(
+        &self,
+        meta: &ParseNestedMeta,
+        logic: impl FnMut(ParseNestedMeta) -> Result<()>,
+    ) -> Result<()> {
+        if !meta.input.peek(token::Paren) {
+            let key = meta.path.get_ident().unwrap();
+            return Err(meta.error(format!("expected `{}({}(...))`", self.attr, key)));
+        }
+        meta.parse_nested_meta(logic)
+    }
+}
+
+/// Fails if both options were given, pointing at `b`. Each option comes with
+/// the attribute it was given in.
+pub fn conflict(a: Option<(&str, &Path)>, b: Option<(&str, &Path)>) -> Result<()> {
+    let (Some((a_attr, a)), Some((b_attr, b))) = (a, b) else {
+        return Ok(());
+    };
+    let name = |attr: &str, path: &Path| {
+        let key = path.get_ident().unwrap();
+        if attr == "builder" {
+            format!("`builder({})`", key)
+        } else {
+            format!("`builder({}({}))`", attr, key)
+        }
+    };
+
+    Err(Error::new_spanned(
+        b,
+        format!(
+            "{} cannot be used together with {}",
+            name(b_attr, b),
+            name(a_attr, a)
+        ),
+    ))
+}
+
+/// The known key closest to a mistyped one, if any is close enough.
+fn suggestion(name: &str, keys: &[&'static str]) -> Option<&'static str> {
+    keys.iter()
+        .map(|key| (distance(name, key), *key))
+        .filter(|(distance, key)| *distance <= key.len().div_ceil(3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, key)| key)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(a != *b);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
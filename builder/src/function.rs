@@ -0,0 +1,530 @@
+use proc_macro2::{Group, Ident, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    parse::Parser, parse_quote, punctuated::Punctuated, Attribute, Error, FieldsNamed, FnArg,
+    GenericParam, Generics, ImplItem, Item, ItemImpl, Lifetime, LifetimeParam, Meta, Pat, Result,
+    ReturnType, Signature, Token, Type, TypePath, Visibility, WhereClause,
+};
+
+use crate::{builder_fields, builder_target, struct_attrs, typestate, Fields, Pattern, Target};
+
+/// The `call()` of a function builder. The typestate builder adds the bounds
+/// on the required arguments and binds the built arguments to `__args`.
+pub struct CallFn {
+    pub sig: TokenStream,
+    pub body: TokenStream,
+}
+
+/// The type and generics of the `impl` block a method is defined in.
+#[derive(Clone, Copy)]
+struct ImplContext<'a> {
+    self_ty: &'a Type,
+    generics: &'a Generics,
+}
+
+/// What `#[builder]` turns a function into: a function of the same name
+/// returning the builder, and the builder itself. The original function stays
+/// behind under a hidden name for `call()` to run.
+struct FnBuilder {
+    start: TokenStream,
+    builder: TokenStream,
+}
+
+pub fn builder_attr(args: TokenStream, item: Item) -> Result<TokenStream> {
+    match item {
+        Item::Fn(mut item) => {
+            if let Some(receiver) = item.sig.receiver() {
+                return Err(Error::new_spanned(
+                    receiver,
+                    "`#[builder]` on a method needs `#[builder]` on its `impl` block as well",
+                ));
+            }
+
+            let FnBuilder { start, builder } =
+                function_builder(args, &mut item.attrs, &mut item.vis, &mut item.sig, None)?;
+            Ok(quote! {
+                #start
+                #item
+                #builder
+            })
+        }
+        Item::Impl(item) => builder_impl(args, item),
+        _ => Err(Error::new(
+            Span::call_site(),
+            "`#[builder]` is only supported on functions and `impl` blocks",
+        )),
+    }
+}
+
+// Items in an `impl` block cannot expand to a struct, so the block is the one
+// expanded and picks out the methods that carry their own `#[builder]`.
+fn builder_impl(args: TokenStream, mut item: ItemImpl) -> Result<TokenStream> {
+    if !args.is_empty() {
+        return Err(Error::new_spanned(
+            args,
+            "options of `#[builder(...)]` go on the methods",
+        ));
+    }
+
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new_spanned(
+            path,
+            "`#[builder]` is only supported on inherent `impl` blocks",
+        ));
+    }
+
+    let context = ImplContext {
+        self_ty: &item.self_ty,
+        generics: &item.generics,
+    };
+    let mut items = vec![];
+    let mut builders = vec![];
+    for impl_item in &item.items {
+        let mut method = match impl_item {
+            ImplItem::Fn(method) => method.clone(),
+            other => {
+                items.push(other.clone());
+                continue;
+            }
+        };
+
+        let position = method
+            .attrs
+            .iter()
+            .position(|attr| attr.path().is_ident("builder"));
+        if let Some(position) = position {
+            let args = match method.attrs.remove(position).meta {
+                Meta::Path(_) => TokenStream::new(),
+                Meta::List(list) => list.tokens,
+                Meta::NameValue(name_value) => {
+                    return Err(Error::new_spanned(name_value, "expected `#[builder(...)]`"))
+                }
+            };
+            let FnBuilder { start, builder } = function_builder(
+                args,
+                &mut method.attrs,
+                &mut method.vis,
+                &mut method.sig,
+                Some(context),
+            )?;
+            items.push(ImplItem::Verbatim(start));
+            builders.push(builder);
+        }
+        items.push(ImplItem::Fn(method));
+    }
+
+    item.items = items;
+    Ok(quote! {
+        #item
+        #(#builders)*
+    })
+}
+
+// The arguments become the fields of a hidden struct, which gets a typestate
+// builder like any derived one. Its `call()` builds the struct and passes the
+// fields on to the original function.
+fn function_builder(
+    args: TokenStream,
+    attrs: &mut Vec<Attribute>,
+    vis: &mut Visibility,
+    sig: &mut Signature,
+    context: Option<ImplContext>,
+) -> Result<FnBuilder> {
+    if let Some(unsafety) = &sig.unsafety {
+        return Err(Error::new_spanned(
+            unsafety,
+            "`#[builder]` is not supported on unsafe functions",
+        ));
+    }
+
+    let mut struct_attrs = if args.is_empty() {
+        Default::default()
+    } else {
+        struct_attrs(&[parse_quote!(#[builder(#args)])])?
+    };
+    let options = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(args)?;
+    let struct_only = [
+        "default",
+        "validate",
+        "serde",
+        "pattern",
+        "typestate",
+        "collect_errors",
+    ];
+    if let Some(meta) = options
+        .iter()
+        .find(|meta| struct_only.iter().any(|key| meta.path().is_ident(key)))
+    {
+        return Err(Error::new_spanned(
+            meta.path(),
+            format!(
+                "`builder({})` is only supported on structs",
+                meta.path().get_ident().unwrap()
+            ),
+        ));
+    }
+    struct_attrs.typestate = true;
+    struct_attrs.pattern = Some(Pattern::Owned);
+
+    let name = sig.ident.clone();
+    let builder_name = match (&struct_attrs.name, &context) {
+        (Some(name), _) => name.clone(),
+        (None, Some(context)) => {
+            format_ident!(
+                "{}{}Builder",
+                type_name(context.self_ty)?,
+                pascal_case(&name)
+            )
+        }
+        (None, None) => format_ident!("{}Builder", pascal_case(&name)),
+    };
+    let args_ident = format_ident!("__{}Args", builder_name);
+    let hidden = format_ident!("__{}_builder", name);
+    let self_ty = context.as_ref().map(|context| context.self_ty);
+
+    // Lifetimes the start function introduces: one for a borrowed receiver,
+    // one for each reference argument without a lifetime of its own.
+    let mut new_lifetimes: Vec<Lifetime> = vec![];
+    let mut fields: Vec<TokenStream> = vec![];
+    let mut call_args: Vec<TokenStream> = vec![];
+    let mut start_receiver = None;
+    let mut receiver_lifetime = None;
+
+    let receiver = format_ident!("__receiver");
+    if let (Some(arg), Some(self_ty)) = (sig.receiver(), self_ty) {
+        let ty = match (&arg.reference, &arg.colon_token) {
+            (Some((_, lifetime)), None) => {
+                let lifetime = lifetime.clone().unwrap_or_else(|| {
+                    let lifetime = Lifetime::new("'__receiver", Span::call_site());
+                    new_lifetimes.push(lifetime.clone());
+                    lifetime
+                });
+                receiver_lifetime = Some(lifetime.clone());
+                let mutability = &arg.mutability;
+                start_receiver = Some(quote!(&#lifetime #mutability self));
+                quote!(&#lifetime #mutability #self_ty)
+            }
+            (None, None) => {
+                start_receiver = Some(quote!(self));
+                quote!(#self_ty)
+            }
+            (_, Some(_)) => {
+                return Err(Error::new_spanned(
+                    arg,
+                    "`#[builder]` methods take `self`, `&self` or `&mut self`",
+                ))
+            }
+        };
+        fields.push(quote!(#[doc(hidden)] #receiver: #ty));
+        call_args.push(quote!(__args.#receiver));
+    }
+
+    for (i, input) in sig.inputs.iter_mut().enumerate() {
+        let arg = match input {
+            FnArg::Typed(arg) => arg,
+            FnArg::Receiver(_) => continue,
+        };
+
+        // `#[builder(...)]` on an argument configures its setter and cannot
+        // stay on the original function.
+        let (builder_attrs, other_attrs): (Vec<Attribute>, Vec<Attribute>) = arg
+            .attrs
+            .drain(..)
+            .partition(|attr| attr.path().is_ident("builder"));
+        arg.attrs = other_attrs;
+
+        let ident = match &*arg.pat {
+            Pat::Ident(pat) if pat.subpat.is_none() => pat.ident.clone(),
+            _ => format_ident!("_{}", i),
+        };
+
+        let mut ty = (*arg.ty).clone();
+        match &mut ty {
+            Type::ImplTrait(_) => {
+                return Err(Error::new_spanned(
+                    &arg.ty,
+                    "`impl Trait` arguments are not supported by `#[builder]`, use a type \
+                     parameter instead",
+                ))
+            }
+            Type::Reference(reference) if reference.lifetime.is_none() => {
+                let lifetime = Lifetime::new(&format!("'__arg{}", i), Span::call_site());
+                reference.lifetime = Some(lifetime.clone());
+                new_lifetimes.push(lifetime);
+            }
+            _ => {}
+        }
+        let ty = match self_ty {
+            Some(self_ty) => replace_self(ty.into_token_stream(), self_ty),
+            None => ty.into_token_stream(),
+        };
+
+        fields.push(quote!(#(#builder_attrs)* #ident: #ty));
+        call_args.push(quote!(__args.#ident));
+    }
+
+    let new_params = new_lifetimes
+        .iter()
+        .map(|lifetime| GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())));
+    let impl_params = context
+        .as_ref()
+        .map(|context| context.generics.params.clone())
+        .unwrap_or_default();
+    let generics = Generics {
+        lt_token: Some(Default::default()),
+        params: lifetimes_first(
+            impl_params
+                .into_iter()
+                .chain(new_params.clone())
+                .chain(sig.generics.params.iter().cloned()),
+        ),
+        gt_token: Some(Default::default()),
+        where_clause: merge_where(
+            context
+                .as_ref()
+                .and_then(|context| context.generics.where_clause.as_ref()),
+            sig.generics.where_clause.as_ref(),
+        ),
+    };
+
+    // Arguments need not use every parameter, and parameters only used in the
+    // return type are not used by any of them.
+    let marker = if generics.params.is_empty() {
+        None
+    } else {
+        let used = generics.params.iter().map(|param| match param {
+            GenericParam::Lifetime(lifetime) => {
+                let lifetime = &lifetime.lifetime;
+                quote!(&#lifetime ())
+            }
+            GenericParam::Type(param) => {
+                let ident = &param.ident;
+                quote!(#ident)
+            }
+            GenericParam::Const(param) => {
+                let ident = &param.ident;
+                quote!([(); #ident])
+            }
+        });
+        let marker = quote!(fn() -> (#(#used,)*));
+        fields.push(quote! {
+            #[builder(skip)]
+            __marker: ::core::marker::PhantomData<#marker>
+        });
+        Some(marker)
+    };
+
+    let fields_named: FieldsNamed = parse_quote!({ #(#fields,)* });
+    let struct_fields: Vec<TokenStream> = fields_named
+        .named
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let ty = &field.ty;
+            quote!(#ident: #ty)
+        })
+        .collect();
+    let fields = Fields::Named(fields_named);
+    let (builder_fields, skipped) = builder_fields(&fields, &struct_attrs, &["call"])?;
+
+    // `call()` has no borrowed arguments, so lifetimes elided in the return
+    // type are given the one they were elided from.
+    let elided = receiver_lifetime.or_else(|| match new_lifetimes.as_slice() {
+        [lifetime] => Some(lifetime.clone()),
+        _ => None,
+    });
+    let ret = match &sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => {
+            let ty = match self_ty {
+                Some(self_ty) => replace_self(ty.into_token_stream(), self_ty),
+                None => ty.into_token_stream(),
+            };
+            match &elided {
+                Some(lifetime) => fill_elided(ty, lifetime),
+                None => ty,
+            }
+        }
+    };
+    let asyncness = &sig.asyncness;
+    let awaited = asyncness.map(|_| quote!(.await));
+    let turbofish = sig
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(&param.ident),
+            GenericParam::Const(param) => Some(&param.ident),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect::<Vec<_>>();
+    let turbofish = if turbofish.is_empty() {
+        None
+    } else {
+        Some(quote!(::<#(#turbofish),*>))
+    };
+    let callee = match self_ty {
+        Some(self_ty) => quote!(<#self_ty>::#hidden #turbofish),
+        None => quote!(#hidden #turbofish),
+    };
+
+    let builder_vis = struct_attrs.vis.clone().unwrap_or_else(|| vis.clone());
+    let target = Target {
+        ident: &args_ident,
+        path: quote!(#args_ident),
+        variant: false,
+        builder_name: builder_name.clone(),
+        error_name: format_ident!("{}Error", builder_name),
+        vis: builder_vis.clone(),
+        builder_fn: format_ident!("builder"),
+        fields: builder_fields,
+        skipped,
+        marker,
+        attrs: &struct_attrs,
+        call: Some(CallFn {
+            sig: quote!(pub #asyncness fn call(self) -> #ret),
+            body: quote!(#callee(#(#call_args),*) #awaited),
+        }),
+    };
+
+    // The start function returns the builder with only the receiver set.
+    let set: Vec<&Ident> = start_receiver.iter().map(|_| &receiver).collect();
+    let start_ty = typestate::builder_state_ty(&target, &generics, &set);
+    let set_receiver = start_receiver.as_ref().map(|_| quote!(.#receiver(self)));
+    let start_generics = Generics {
+        lt_token: Some(Default::default()),
+        params: lifetimes_first(new_params.chain(sig.generics.params.iter().cloned())),
+        gt_token: Some(Default::default()),
+        where_clause: sig.generics.where_clause.clone(),
+    };
+    let (_, _, start_where) = start_generics.split_for_impl();
+    let (docs, other_attrs): (Vec<Attribute>, Vec<Attribute>) = attrs
+        .drain(..)
+        .partition(|attr| attr.path().is_ident("doc"));
+    let start = quote! {
+        #(#docs)*
+        #vis fn #name #start_generics(#start_receiver) -> #start_ty #start_where {
+            #args_ident::builder() #set_receiver
+        }
+    };
+
+    let builder = builder_target(&target, &generics);
+    let (_, _, where_clause) = generics.split_for_impl();
+    let builder = quote! {
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        #builder_vis struct #args_ident #generics #where_clause {
+            #(#struct_fields,)*
+        }
+
+        #builder
+    };
+
+    // The original function, under a name of its own.
+    *attrs = other_attrs;
+    attrs.push(parse_quote!(#[doc(hidden)]));
+    *vis = Visibility::Inherited;
+    sig.ident = hidden;
+
+    Ok(FnBuilder { start, builder })
+}
+
+/// Puts lifetimes in front of the other parameters, as Rust requires.
+fn lifetimes_first(
+    params: impl Iterator<Item = GenericParam>,
+) -> Punctuated<GenericParam, Token![,]> {
+    let (lifetimes, others): (Vec<_>, Vec<_>) =
+        params.partition(|param| matches!(param, GenericParam::Lifetime(_)));
+    lifetimes.into_iter().chain(others).collect()
+}
+
+fn merge_where(a: Option<&WhereClause>, b: Option<&WhereClause>) -> Option<WhereClause> {
+    let predicates: Punctuated<_, Token![,]> = a
+        .into_iter()
+        .chain(b)
+        .flat_map(|clause| clause.predicates.iter().cloned())
+        .collect();
+    if predicates.is_empty() {
+        None
+    } else {
+        Some(parse_quote!(where #predicates))
+    }
+}
+
+/// `Self` means the builder inside its impls, so it is spelled out.
+fn replace_self(tokens: TokenStream, self_ty: &Type) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Ident(ident) if ident == "Self" => self_ty.to_token_stream(),
+            TokenTree::Group(group) => {
+                let mut replaced =
+                    Group::new(group.delimiter(), replace_self(group.stream(), self_ty));
+                replaced.set_span(group.span());
+                TokenTree::Group(replaced).into_token_stream()
+            }
+            other => other.into_token_stream(),
+        })
+        .collect()
+}
+
+/// Gives `&T` and `'_` in `tokens` the lifetime `lifetime`.
+fn fill_elided(tokens: TokenStream, lifetime: &Lifetime) -> TokenStream {
+    let mut filled = TokenStream::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) if punct.as_char() == '&' => {
+                filled.extend([TokenTree::Punct(punct)]);
+                let named = matches!(
+                    tokens.peek(),
+                    Some(TokenTree::Punct(next)) if next.as_char() == '\''
+                );
+                if !named {
+                    filled.extend(lifetime.to_token_stream());
+                }
+            }
+            TokenTree::Punct(punct) if punct.as_char() == '\'' => {
+                let name = tokens.next();
+                match &name {
+                    Some(TokenTree::Ident(ident)) if ident == "_" => {
+                        filled.extend(lifetime.to_token_stream())
+                    }
+                    _ => filled.extend([TokenTree::Punct(punct)].into_iter().chain(name)),
+                }
+            }
+            TokenTree::Group(group) => {
+                let mut replaced =
+                    Group::new(group.delimiter(), fill_elided(group.stream(), lifetime));
+                replaced.set_span(group.span());
+                filled.extend([TokenTree::Group(replaced)]);
+            }
+            other => filled.extend([other]),
+        }
+    }
+    filled
+}
+
+fn type_name(ty: &Type) -> Result<&Ident> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => Ok(&path.segments.last().unwrap().ident),
+        _ => Err(Error::new_spanned(
+            ty,
+            "`#[builder]` is only supported in `impl` blocks of named types",
+        )),
+    }
+}
+
+fn pascal_case(ident: &Ident) -> String {
+    ident
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
@@ -1,337 +1,1882 @@
-use proc_macro2::{Ident, TokenStream};
+mod attrs;
+mod function;
+mod typestate;
+
+use attrs::Options;
+use proc_macro2::{Ident, Span, TokenStream};
 use quote::{format_ident, quote, quote_spanned};
 use syn::{
-    parse_macro_input, punctuated::Punctuated, spanned::Spanned, AngleBracketedGenericArguments,
-    Data, DeriveInput, Error, Expr, ExprLit, Fields, GenericArgument, Lit, Meta, Path,
-    PathArguments, Result, Token, Type, TypePath,
+    meta::ParseNestedMeta, parse_macro_input, parse_quote, punctuated::Punctuated,
+    spanned::Spanned, token, AngleBracketedGenericArguments, Attribute, Data, DeriveInput, Error,
+    Expr, Fields, GenericArgument, Generics, Index, Member, Meta, Path, PathArguments, Result,
+    Type, TypeGenerics, TypePath, Visibility, WhereClause,
 };
 
-#[proc_macro_derive(Builder, attributes(builder))]
+#[proc_macro_derive(Builder, attributes(builder, builder_attr))]
 pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
 
-    let ident = input.ident;
-
-    let builder_fields = match builder_field(&input.data, &ident) {
-        Ok(field) => field,
+    let struct_attrs = match struct_attrs(&input.attrs) {
+        Ok(attrs) => attrs,
         Err(err) => return err.into_compile_error().into(),
     };
 
-    let builder_setter = match builder_setter(&input.data, &ident) {
-        Ok(setter) => setter,
+    let targets = match builder_targets(&input, &struct_attrs) {
+        Ok(targets) => targets,
         Err(err) => return err.into_compile_error().into(),
     };
 
-    let builder_fn = match builder_fn(&input.data, &ident) {
-        Ok(function) => function,
-        Err(err) => return err.into_compile_error().into(),
-    };
+    let builders = targets
+        .iter()
+        .map(|target| builder_target(target, &input.generics));
 
-    let default_builder_init = match builder_field_default(&input.data, &ident) {
-        Ok(default_fields) => default_fields,
-        Err(err) => return err.into_compile_error().into(),
-    };
+    proc_macro::TokenStream::from(quote! {
+        #(#builders)*
+    })
+}
+
+/// Named and optional arguments for a function: `#[builder] fn connect(..)`
+/// turns `connect` into a builder whose setters take the arguments and whose
+/// `call()` runs the original body. Methods additionally need `#[builder]` on
+/// their `impl` block.
+#[proc_macro_attribute]
+pub fn builder(
+    args: proc_macro::TokenStream,
+    input: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let item = parse_macro_input!(input as syn::Item);
+    match function::builder_attr(args.into(), item) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+/// A builder to generate, for a struct or for one variant of an enum.
+struct Target<'a> {
+    /// The type being built.
+    ident: &'a Ident,
+    /// Names the built value in a struct expression, `Shape` or `Shape::Circle`.
+    path: TokenStream,
+    /// Whether this builds an enum variant rather than a struct.
+    variant: bool,
+    builder_name: Ident,
+    error_name: Ident,
+    /// Visibility of the builder, its error type and the function creating it.
+    vis: Visibility,
+    /// The associated function of `ident` that returns a new builder.
+    builder_fn: Ident,
+    fields: Vec<BuilderField<'a>>,
+    /// `#[builder(skip)]` fields, which only show up in `build()`.
+    skipped: Vec<BuilderField<'a>>,
+    /// A variant need not use every parameter of its enum, so enum builders
+    /// hold on to all of them through a `PhantomData` of this type.
+    marker: Option<TokenStream>,
+    attrs: &'a StructAttrs,
+    /// Set for the builders of `#[builder]` functions, which get a `call()`.
+    call: Option<function::CallFn>,
+}
+
+impl Target<'_> {
+    /// Derives and attributes forwarded onto the builder struct.
+    fn builder_attrs(&self, generics: &Generics) -> TokenStream {
+        let derive = if self.attrs.derives.is_empty() {
+            None
+        } else {
+            let derives = &self.attrs.derives;
+            Some(quote!(#[derive(#(#derives),*)]))
+        };
+        let forward = &self.attrs.forward;
+
+        // Fields missing from the input keep the value of a new builder.
+        let serde = self.serde().then(|| {
+            let ident = self.ident;
+            let builder_fn = &self.builder_fn;
+            let (_, ty_generics, _) = generics.split_for_impl();
+            let turbofish = ty_generics.as_turbofish();
+            let default = quote!(#ident #turbofish :: #builder_fn).to_string();
+            quote! {
+                #[derive(::serde::Deserialize)]
+                #[serde(default = #default)]
+            }
+        });
+
+        quote! {
+            #derive
+            #serde
+            #(#[#forward])*
+        }
+    }
+
+    fn marker_field(&self) -> Option<TokenStream> {
+        let marker = self.marker.as_ref()?;
+        let skip = self.serde().then(|| quote!(#[serde(skip)]));
+        Some(quote!(#skip __marker: ::core::marker::PhantomData<#marker>,))
+    }
+
+    /// Whether the builder derives `Deserialize` and gets `merge`.
+    fn serde(&self) -> bool {
+        self.attrs.serde
+    }
+
+    fn marker_init(&self) -> Option<TokenStream> {
+        self.marker
+            .as_ref()
+            .map(|_| quote!(__marker: ::core::marker::PhantomData,))
+    }
+
+    /// The values `build()` fills the skipped fields with.
+    fn skipped_init(&self) -> impl Iterator<Item = TokenStream> + '_ {
+        self.skipped.iter().map(|f| {
+            let member = &f.member;
+            let value = match &f.attrs.default {
+                Some(FieldDefault::Expr(expr)) => quote!(#expr),
+                None if self.attrs.default => quote!(__default.#member),
+                _ => quote!(::core::default::Default::default()),
+            };
+            quote_spanned! {f.span=>
+                #member: #value
+            }
+        })
+    }
+}
+
+fn builder_targets<'a>(
+    input: &'a DeriveInput,
+    struct_attrs: &'a StructAttrs,
+) -> Result<Vec<Target<'a>>> {
+    let ident = &input.ident;
+    let vis = struct_attrs
+        .vis
+        .clone()
+        .unwrap_or_else(|| input.vis.clone());
+    match input.data {
+        Data::Struct(ref data) => {
+            let builder_name = match &struct_attrs.name {
+                Some(name) => name.clone(),
+                None => format_ident!("{}Builder", ident),
+            };
+            let error_name = format_ident!("{}Error", builder_name);
+            let (fields, skipped) = builder_fields(&data.fields, struct_attrs, &[])?;
+            Ok(vec![Target {
+                ident,
+                path: quote!(#ident),
+                variant: false,
+                builder_name,
+                error_name,
+                vis,
+                builder_fn: format_ident!("builder"),
+                fields,
+                skipped,
+                marker: None,
+                attrs: struct_attrs,
+                call: None,
+            }])
+        }
+        Data::Enum(ref data) => {
+            if struct_attrs.default {
+                return Err(Error::new_spanned(
+                    ident,
+                    "`builder(default)` is only supported on structs",
+                ));
+            }
+
+            if let Some(name) = &struct_attrs.name {
+                return Err(Error::new_spanned(
+                    name,
+                    "`builder(name = \"...\")` is only supported on structs, \
+                     enum builders are named after their variant",
+                ));
+            }
+
+            let (_, ty_generics, _) = input.generics.split_for_impl();
+            let marker = if input.generics.params.is_empty() {
+                None
+            } else {
+                Some(quote!(fn() -> #ident #ty_generics))
+            };
+
+            let mut targets = vec![];
+            for variant in &data.variants {
+                if let Fields::Unit = variant.fields {
+                    continue;
+                }
+
+                let variant_ident = &variant.ident;
+                let (fields, skipped) = builder_fields(&variant.fields, struct_attrs, &[])?;
+                targets.push(Target {
+                    ident,
+                    path: quote!(#ident::#variant_ident),
+                    variant: true,
+                    builder_name: format_ident!("{}{}Builder", ident, variant_ident),
+                    error_name: format_ident!("{}{}BuilderError", ident, variant_ident),
+                    vis: vis.clone(),
+                    builder_fn: format_ident!("{}_builder", snake_case(variant_ident)),
+                    fields,
+                    skipped,
+                    marker: marker.clone(),
+                    attrs: struct_attrs,
+                    call: None,
+                });
+            }
+            Ok(targets)
+        }
+        Data::Union(_) => Err(Error::new_spanned(ident, "only support struct and enum")),
+    }
+}
+
+fn builder_target(target: &Target, generics: &Generics) -> TokenStream {
+    let struct_attrs = target.attrs;
+    if struct_attrs.typestate {
+        if struct_attrs.collect_errors {
+            return Error::new_spanned(
+                target.ident,
+                "`builder(collect_errors)` has no effect together with `builder(typestate)`",
+            )
+            .into_compile_error();
+        }
+
+        if !matches!(struct_attrs.pattern, None | Some(Pattern::Owned)) {
+            return Error::new_spanned(
+                target.ident,
+                "`builder(typestate)` builders always use `pattern = \"owned\"`",
+            )
+            .into_compile_error();
+        }
 
-    let builder_name = format_ident!("{}Builder", ident);
+        // A typestate builder cannot be partially filled in.
+        if struct_attrs.serde {
+            return Error::new_spanned(
+                target.ident,
+                "`builder(serde)` cannot be used together with `builder(typestate)`",
+            )
+            .into_compile_error();
+        }
+
+        if let Some(f) = target
+            .fields
+            .iter()
+            .find(|f| matches!(f.kind, FieldKind::SubBuilder(_)))
+        {
+            return Error::new(
+                f.span,
+                "`builder(sub_builder)` cannot be used together with `builder(typestate)`",
+            )
+            .into_compile_error();
+        }
+
+        return typestate::builder_typestate(target, generics);
+    }
+
+    let ident = target.ident;
+    let builder_name = &target.builder_name;
+    let builder_fn_name = &target.builder_fn;
+    let vis = &target.vis;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let builder_attrs = target.builder_attrs(generics);
+    let builder_fields = builder_field(target);
+    let builder_setter = builder_setter(target, struct_attrs.pattern());
+    let builder_merge = builder_merge(target);
+    let builder_inspect = builder_inspect(target);
+    let builder_fn = builder_fn(target, &ty_generics);
+    let default_builder_init = builder_field_default(target);
+
+    let builder_error = builder_error(target);
+    let builder_from = builder_from(target, generics, &quote!(#builder_name #ty_generics), None);
+    let builder_nested = builder_nested(target, generics);
     let default_builder = quote! {
         #builder_name {
            #default_builder_init
         }
     };
 
-    let builder = quote! {
-        use std::error::Error;
-        impl #ident {
-            fn builder() -> #builder_name {
+    quote! {
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #vis fn #builder_fn_name() -> #builder_name #ty_generics {
                 #default_builder
             }
         }
 
-        struct #builder_name {
+        #builder_attrs
+        #vis struct #builder_name #generics #where_clause {
           #builder_fields
         }
 
-        impl #builder_name {
+        impl #impl_generics #builder_name #ty_generics #where_clause {
             #builder_setter
 
+            #builder_merge
+
+            #builder_inspect
+
             #builder_fn
         }
+
+        #builder_from
+        #builder_nested
+        #builder_error
+    }
+}
+
+/// The hidden methods through which `build()` and `to_builder()` of an outer
+/// builder handle this one as a `sub_builder` field. Building through a
+/// borrowed builder whose own `build()` takes more than that builds a clone,
+/// which only the outer builders that need it require.
+fn builder_nested(target: &Target, generics: &Generics) -> Option<TokenStream> {
+    if target.variant || target.call.is_some() {
+        return None;
+    }
+
+    let ident = target.ident;
+    let builder_name = &target.builder_name;
+    let error_name = &target.error_name;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let built = quote!(::core::result::Result<#ident #ty_generics, #error_name>);
+    let clone = quote! {
+        where
+            for<'__b> Self: ::core::clone::Clone,
+        {
+            let mut __builder = ::core::clone::Clone::clone(self);
+            __builder.build()
+        }
+    };
+    let (by_ref, by_mut, owned) = match target.attrs.pattern() {
+        Pattern::Mutable => (
+            clone,
+            quote!({ self.build() }),
+            quote!({
+                let mut __builder = self;
+                __builder.build()
+            }),
+        ),
+        Pattern::Owned => (clone.clone(), clone, quote!({ self.build() })),
+        Pattern::Immutable => (
+            quote!({ self.build() }),
+            quote!({ self.build() }),
+            quote!({ self.build() }),
+        ),
     };
 
-    proc_macro::TokenStream::from(builder)
+    Some(quote! {
+        impl #impl_generics #builder_name #ty_generics #where_clause {
+            #[doc(hidden)]
+            pub fn __from_value(value: #ident #ty_generics) -> Self {
+                ::core::convert::From::from(value)
+            }
+
+            #[doc(hidden)]
+            pub fn __build_ref(&self) -> #built #by_ref
+
+            #[doc(hidden)]
+            pub fn __build_mut(&mut self) -> #built #by_mut
+
+            #[doc(hidden)]
+            pub fn __build_owned(self) -> #built #owned
+        }
+    })
 }
 
-fn builder_field(data: &Data, ident: &Ident) -> Result<TokenStream> {
-    match *data {
-        Data::Struct(ref data) => match data.fields {
-            Fields::Named(ref fields) => {
-                let recurse = fields.named.iter().map(|f| {
-                    let ident = match f.ident.as_ref() {
-                        Some(ident) => ident,
-                        None => {
-                            return Error::new_spanned(ident, "anyonymous filed is not support")
-                                .into_compile_error()
-                        }
-                    };
+/// `merge`, which takes over every field that is set in another builder.
+/// Collections are extended and nested builders merged in turn. A collection
+/// with a default that is still unset in both stays unset.
+fn builder_merge(target: &Target) -> Option<TokenStream> {
+    if !target.serde() {
+        return None;
+    }
 
-                    if let Some(ty) = is_type(&f.ty, "Vec") {
-                        quote_spanned! {f.span()=>
-                            #ident : std::vec::Vec<#ty>
-                        }
-                    } else {
-                        let ty = match is_type(&f.ty, "Option") {
-                            Some(ty) => ty,
-                            None => &f.ty,
-                        };
-                        quote_spanned! {f.span()=>
-                            #ident : std::option::Option<#ty>
+    let fields = target.fields.iter().map(|f| {
+        let ident = &f.ident;
+        match f.kind {
+            FieldKind::Required(_) | FieldKind::Optional(_) => quote_spanned! {f.span=>
+                if let ::core::option::Option::Some(__value) = other.#ident {
+                    self.#ident = ::core::option::Option::Some(__value);
+                }
+            },
+            FieldKind::Repeated { .. } => match f.collection_default() {
+                Some(default) => quote_spanned! {f.span=>
+                    if let ::core::option::Option::Some(__items) = other.#ident {
+                        ::core::iter::Extend::extend(
+                            ::core::option::Option::get_or_insert_with(&mut self.#ident, || #default),
+                            __items,
+                        );
+                    }
+                },
+                None => quote_spanned! {f.span=>
+                    ::core::iter::Extend::extend(&mut self.#ident, other.#ident);
+                },
+            },
+            FieldKind::SubBuilder(_) => quote_spanned! {f.span=>
+                self.#ident.merge(other.#ident);
+            },
+        }
+    });
+
+    Some(quote! {
+        /// Takes over the fields set in `other`, e.g. to layer configuration
+        /// sources on top of each other before building.
+        pub fn merge(&mut self, other: Self) -> &mut Self {
+            #(#fields)*
+            self
+        }
+    })
+}
+
+/// `x_ref` and `clear_x` per field, plus `is_complete` and `missing_fields`,
+/// which let a partially filled builder be inspected before `build()`.
+///
+/// A cleared field goes back to the value of a new builder. Typestate builders
+/// track their required fields in the type, so those cannot be cleared.
+fn builder_inspect(target: &Target) -> TokenStream {
+    let alloc = target.attrs.alloc();
+    let mut accessors = vec![];
+    let mut missing = vec![];
+    let mut complete = vec![];
+    for f in &target.fields {
+        let ident = &f.ident;
+        let name = ident.to_string();
+        let required = field_value(f, target, true).is_none();
+        // Accessors of a hidden field are hidden along with its setter.
+        let hidden = f
+            .docs
+            .iter()
+            .filter(|attr| matches!(attr.meta, Meta::List(_)));
+
+        let field_ref = match f.kind {
+            FieldKind::Required(ty) | FieldKind::Optional(ty) => Some((
+                quote!(#ty),
+                quote!(::core::option::Option::as_ref(&self.#ident)),
+            )),
+            FieldKind::Repeated { ty, .. } if f.collection_default().is_none() => Some((
+                quote!(#ty),
+                quote!(::core::option::Option::Some(&self.#ident)),
+            )),
+            FieldKind::Repeated { ty, .. } => Some((
+                quote!(#ty),
+                quote!(::core::option::Option::as_ref(&self.#ident)),
+            )),
+            FieldKind::SubBuilder(_) => None,
+        };
+        if let Some((ty, value)) = field_ref {
+            let ref_name = format_ident!("{}_ref", ident);
+            let doc = format!("The value of `{}` set so far.", name);
+            let hidden = hidden.clone();
+            accessors.push(quote_spanned! {f.span=>
+                #[doc = #doc]
+                #(#hidden)*
+                pub fn #ref_name(&self) -> ::core::option::Option<&#ty> {
+                    #value
+                }
+            });
+        }
+
+        if !(required && target.attrs.typestate) {
+            let clear_name = format_ident!("clear_{}", ident);
+            let doc = format!("Unsets `{}` again.", name);
+            let value = field_init(f);
+            accessors.push(quote_spanned! {f.span=>
+                #[doc = #doc]
+                #(#hidden)*
+                pub fn #clear_name(&mut self) {
+                    self.#ident = #value;
+                }
+            });
+        }
+
+        if let FieldKind::SubBuilder(_) = f.kind {
+            complete.push(quote!(self.#ident.is_complete()));
+            missing.push(quote_spanned! {f.span=>
+                if !self.#ident.is_complete() {
+                    #alloc::vec::Vec::push(&mut __missing, #name);
+                }
+            });
+        } else if required {
+            complete.push(quote!(::core::option::Option::is_some(&self.#ident)));
+            missing.push(quote_spanned! {f.span=>
+                if ::core::option::Option::is_none(&self.#ident) {
+                    #alloc::vec::Vec::push(&mut __missing, #name);
+                }
+            });
+        }
+    }
+
+    if complete.is_empty() {
+        complete.push(quote!(true));
+    }
+
+    quote! {
+        #(#accessors)*
+
+        /// Whether every field without a default has been set.
+        pub fn is_complete(&self) -> bool {
+            #(#complete)&&*
+        }
+
+        /// The fields without a default that have not been set yet.
+        pub fn missing_fields(&self) -> #alloc::vec::Vec<&'static str> {
+            #[allow(unused_mut)]
+            let mut __missing = #alloc::vec::Vec::new();
+            #(#missing)*
+            __missing
+        }
+    }
+}
+
+/// `impl From<T> for XBuilder` and `T::to_builder(&self)`, which start a
+/// builder from an existing struct with every field set. Enum variants cannot
+/// be converted infallibly, so they get neither.
+///
+/// `to_builder` clones each field. Its bounds are higher-ranked so that they
+/// are only checked where it is called, not for every struct.
+fn builder_from(
+    target: &Target,
+    generics: &Generics,
+    builder_ty: &TokenStream,
+    state_init: Option<TokenStream>,
+) -> Option<TokenStream> {
+    // Variants and function arguments have no value to start a builder from.
+    if target.variant || target.call.is_some() {
+        return None;
+    }
+
+    let ident = target.ident;
+    let vis = &target.vis;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let from_value = builder_init_from(target, &state_init, &|member| quote!(value.#member));
+    let from_ref = builder_init_from(
+        target,
+        &state_init,
+        &|member| quote!(::core::clone::Clone::clone(&self.#member)),
+    );
+
+    let mut clone_bounds = generics
+        .where_clause
+        .clone()
+        .unwrap_or_else(|| WhereClause {
+            where_token: Default::default(),
+            predicates: Punctuated::new(),
+        });
+    for f in &target.fields {
+        let ty = f.ty;
+        clone_bounds
+            .predicates
+            .push(parse_quote!(for<'__b> #ty: ::core::clone::Clone));
+    }
+
+    Some(quote! {
+        impl #impl_generics ::core::convert::From<#ident #ty_generics> for #builder_ty #where_clause {
+            fn from(value: #ident #ty_generics) -> Self {
+                #from_value
+            }
+        }
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #vis fn to_builder(&self) -> #builder_ty #clone_bounds {
+                #from_ref
+            }
+        }
+    })
+}
+
+/// A builder holding the values of a struct's fields, as given by `value`.
+fn builder_init_from(
+    target: &Target,
+    state_init: &Option<TokenStream>,
+    value: &dyn Fn(&Member) -> TokenStream,
+) -> TokenStream {
+    let builder_name = &target.builder_name;
+    let fields = target.fields.iter().map(|f| {
+        let ident = &f.ident;
+        let value = value(&f.member);
+        match f.kind {
+            FieldKind::Optional(_) => quote_spanned! {f.span=>
+                #ident: #value
+            },
+            FieldKind::Repeated { .. } if f.collection_default().is_none() => {
+                quote_spanned! {f.span=>
+                    #ident: #value
+                }
+            }
+            FieldKind::Required(_) | FieldKind::Repeated { .. } => quote_spanned! {f.span=>
+                #ident: ::core::option::Option::Some(#value)
+            },
+            FieldKind::SubBuilder(ref builder) => quote_spanned! {f.span=>
+                #ident: <#builder>::__from_value(#value)
+            },
+        }
+    });
+    quote! {
+        #builder_name {
+            #state_init
+            #(#fields,)*
+        }
+    }
+}
+
+/// Options given on the struct itself, e.g. `#[builder(collect_errors)]`.
+#[derive(Default)]
+struct StructAttrs {
+    collect_errors: bool,
+    /// Fill every unset field from the struct's own `Default` impl.
+    default: bool,
+    /// Check for missing required fields at compile time instead of in `build()`.
+    typestate: bool,
+    pattern: Option<Pattern>,
+    setter: SetterAttrs,
+    /// `fn(&T) -> Result<(), String>` run by `build()` on the finished struct.
+    validate: Option<Path>,
+    /// Overrides the visibility of the struct for the builder.
+    vis: Option<Visibility>,
+    /// Overrides the `{Struct}Builder` name.
+    name: Option<Ident>,
+    /// `#[builder(derive(...))]`, derived on the builder.
+    derives: Vec<Path>,
+    /// The contents of every `#[builder_attr(...)]`, put on the builder.
+    forward: Vec<TokenStream>,
+    /// Take `Vec` and `String` from `::alloc` instead of `::std`.
+    no_std: bool,
+    /// `#[builder(try_setter)]` for every field.
+    try_setter: bool,
+    /// `#[builder(serde)]`, only with the `serde` feature.
+    serde: bool,
+}
+
+impl StructAttrs {
+    fn pattern(&self) -> Pattern {
+        self.pattern.unwrap_or_default()
+    }
+
+    /// The crate `Vec` and `String` are named through.
+    fn alloc(&self) -> TokenStream {
+        if self.no_std {
+            quote!(::alloc)
+        } else {
+            quote!(::std)
+        }
+    }
+}
+
+/// How setters and `build()` take the builder, `#[builder(pattern = "...")]`.
+#[derive(Default, Clone, Copy, PartialEq)]
+enum Pattern {
+    /// `fn field(&mut self, ..) -> &mut Self`
+    #[default]
+    Mutable,
+    /// `fn field(self, ..) -> Self`, `build(self)` moves the fields out.
+    Owned,
+    /// `fn field(&self, ..) -> Self`, every setter returns a modified copy.
+    Immutable,
+}
+
+const STRUCT_KEYS: &[&str] = &[
+    "collect_errors",
+    "default",
+    "typestate",
+    "pattern",
+    "setter",
+    "validate",
+    "vis",
+    "name",
+    "derive",
+    "no_std",
+    "try_setter",
+    "serde",
+];
+const FIELD_KEYS: &[&str] = &[
+    "each",
+    "default",
+    "setter",
+    "name",
+    "optional",
+    "required",
+    "skip",
+    "try_setter",
+    "sub_builder",
+];
+const SETTER_KEYS: &[&str] = &["into", "strip_option", "name", "prefix", "skip"];
+
+fn struct_attrs(attrs: &[Attribute]) -> Result<StructAttrs> {
+    let mut struct_attrs = StructAttrs::default();
+    let mut options = Options::new("builder", STRUCT_KEYS).misplaced(FIELD_KEYS, "on fields");
+    let mut setter_options = Options::new("setter", &["into", "strip_option", "prefix"])
+        .misplaced(&["skip"], "on fields");
+    for attr in attrs {
+        if attr.path().is_ident("builder_attr") {
+            struct_attrs
+                .forward
+                .push(attr.meta.require_list()?.tokens.clone());
+            continue;
+        }
+
+        if !attr.path().is_ident("builder") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            match options.key(&meta)? {
+                "collect_errors" => struct_attrs.collect_errors = options.bool(&meta)?,
+                "default" => struct_attrs.default = options.bool(&meta)?,
+                "typestate" => struct_attrs.typestate = options.bool(&meta)?,
+                "no_std" => struct_attrs.no_std = options.bool(&meta)?,
+                "try_setter" => struct_attrs.try_setter = options.bool(&meta)?,
+                "serde" => {
+                    if !cfg!(feature = "serde") {
+                        return Err(meta.error(
+                            "`builder(serde)` requires the `serde` feature of derive_builder",
+                        ));
+                    }
+                    struct_attrs.serde = options.bool(&meta)?;
+                }
+                "pattern" => {
+                    let lit = options.string(&meta)?;
+                    struct_attrs.pattern = Some(match lit.value().as_str() {
+                        "mutable" => Pattern::Mutable,
+                        "owned" => Pattern::Owned,
+                        "immutable" => Pattern::Immutable,
+                        _ => {
+                            return Err(Error::new_spanned(
+                                lit,
+                                "expected `\"owned\"`, `\"mutable\"` or `\"immutable\"`",
+                            ))
                         }
+                    });
+                }
+                "validate" => struct_attrs.validate = Some(options.string(&meta)?.parse()?),
+                "vis" => struct_attrs.vis = Some(options.string(&meta)?.parse()?),
+                "name" => struct_attrs.name = Some(options.string(&meta)?.parse()?),
+                "derive" => options.list(&meta, |meta| {
+                    struct_attrs.derives.push(meta.path);
+                    Ok(())
+                })?,
+                "setter" => options.list(&meta, |meta| {
+                    if meta.path.is_ident("name") {
+                        return Err(meta.error(
+                            "`setter(name = \"...\")` is only supported on fields, \
+                             use `setter(prefix = \"...\")` here",
+                        ));
                     }
-                });
+                    let key = setter_options.key(&meta)?;
+                    setter_attrs(key, meta, &setter_options, &mut struct_attrs.setter)
+                })?,
+                _ => unreachable!(),
+            }
+            Ok(())
+        })?;
+    }
 
-                Ok(quote! {
-                    #(#recurse),*
-                })
+    Ok(struct_attrs)
+}
+
+/// A field of the input struct together with its `#[builder(...)]` options.
+struct BuilderField<'a> {
+    /// Name of the field on the builder.
+    ident: Ident,
+    /// Name of the setter, `setter(name = "...")` or the prefixed `ident`.
+    setter: Ident,
+    /// The field's doc comments, repeated on its setters.
+    docs: Vec<&'a Attribute>,
+    /// The field of the built struct, a name or a tuple index.
+    member: Member,
+    ty: &'a Type,
+    kind: FieldKind<'a>,
+    attrs: FieldAttrs,
+    span: Span,
+}
+
+impl BuilderField<'_> {
+    /// The `default = "..."` of a collection, which is only used when the
+    /// collection is never set or added to. Until then the builder holds
+    /// `None` for it rather than the default items, so that builders merged
+    /// into it do not add them again.
+    fn collection_default(&self) -> Option<&Expr> {
+        match (&self.kind, &self.attrs.default) {
+            (FieldKind::Repeated { .. }, Some(FieldDefault::Expr(expr))) => Some(expr),
+            _ => None,
+        }
+    }
+}
+
+enum FieldKind<'a> {
+    /// Has to be set before `build()`, unless it has a default.
+    Required(&'a Type),
+    /// `Option<T>`, left as `None` when never set.
+    Optional(&'a Type),
+    /// `Vec<T>`, or any collection with `#[builder(each = "...")]`. Starts out
+    /// empty, or unset if it has a default, and can be extended one item at a
+    /// time.
+    Repeated { ty: &'a Type, item: Item<'a> },
+    /// `#[builder(sub_builder)]`, holds the field's own builder, e.g.
+    /// `ServerBuilder` for a `Server` field, which `build()` builds in turn.
+    /// Named by `sub_builder(builder = "...")` when that does not fit.
+    SubBuilder(Type),
+}
+
+/// What a single call of an `each` setter adds to a collection.
+enum Item<'a> {
+    /// `Vec<T>`, `HashSet<T>`, `VecDeque<T>`, ...
+    Single(&'a Type),
+    /// `HashMap<K, V>`, `BTreeMap<K, V>`, ...
+    Entry(&'a Type, &'a Type),
+}
+
+/// Options given on a single field, e.g. `#[builder(each = "arg")]`.
+#[derive(Default)]
+struct FieldAttrs {
+    each: Option<String>,
+    default: Option<FieldDefault>,
+    setter: SetterAttrs,
+    /// Builder name of a tuple struct field, `_0`, `_1`, ... otherwise.
+    name: Option<Ident>,
+    /// `#[builder(optional)]` or `#[builder(required)]`, for types that are
+    /// not recognized as `Option<T>` or are wrongly taken for it.
+    kind: Option<KindOverride>,
+    /// `#[builder(skip)]` or `#[builder(setter(skip))]`, no setter and the
+    /// field is filled from its default.
+    skip: bool,
+    /// Also generate `try_field`, taking any `V: TryInto<T>`.
+    try_setter: bool,
+    sub_builder: bool,
+    /// `#[builder(sub_builder(builder = "..."))]`, the nested builder's type
+    /// when it is not the field type's name followed by `Builder`.
+    sub_builder_ty: Option<Type>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum KindOverride {
+    Optional,
+    Required,
+}
+
+/// `#[builder(setter(...))]`, a field's own options take precedence over the
+/// ones given on the struct.
+#[derive(Default)]
+struct SetterAttrs {
+    /// Setters accept any `V: Into<T>`.
+    into: Option<bool>,
+    /// Setters of `Option<T>` fields take `T` instead of `Option<T>`.
+    strip_option: Option<bool>,
+    /// Replaces the setter name, fields only.
+    name: Option<Ident>,
+    /// Put in front of the field name to get the setter name, e.g. `with_`.
+    prefix: Option<String>,
+}
+
+impl SetterAttrs {
+    fn accepts_into(&self, defaults: &SetterAttrs) -> bool {
+        self.into.or(defaults.into).unwrap_or(false)
+    }
+
+    fn strip_option(&self, defaults: &SetterAttrs) -> bool {
+        self.strip_option.or(defaults.strip_option).unwrap_or(true)
+    }
+
+    fn prefix<'a>(&'a self, defaults: &'a SetterAttrs) -> Option<&'a str> {
+        self.prefix.as_deref().or(defaults.prefix.as_deref())
+    }
+}
+
+enum FieldDefault {
+    /// `#[builder(default)]`, uses `Default::default()`.
+    Trait,
+    /// `#[builder(default = "...")]`
+    Expr(Expr),
+}
+
+/// The fields of a struct or variant, and separately the skipped ones.
+/// `methods` are the builder's own methods besides the ones every builder has.
+fn builder_fields<'a>(
+    fields: &'a Fields,
+    struct_attrs: &StructAttrs,
+    methods: &[&str],
+) -> Result<(Vec<BuilderField<'a>>, Vec<BuilderField<'a>>)> {
+    let setter_defaults = &struct_attrs.setter;
+    let mut results = vec![];
+    for (i, f) in fields.iter().enumerate() {
+        let attrs = builder_field_attrs(f)?;
+        let (ident, member) = match (&f.ident, &attrs.name) {
+            (Some(ident), None) => (ident.clone(), Member::Named(ident.clone())),
+            (Some(_), Some(name)) => {
+                return Err(Error::new_spanned(
+                    name,
+                    "`builder(name = \"...\")` is only supported on tuple struct fields",
+                ))
+            }
+            (None, name) => (
+                name.clone().unwrap_or_else(|| format_ident!("_{}", i)),
+                Member::Unnamed(Index::from(i)),
+            ),
+        };
+
+        let kind = match attrs.kind {
+            _ if attrs.sub_builder => FieldKind::SubBuilder(match &attrs.sub_builder_ty {
+                Some(builder) => builder.clone(),
+                None => sub_builder_ty(&f.ty)?,
+            }),
+            Some(KindOverride::Required) => FieldKind::Required(&f.ty),
+            Some(KindOverride::Optional) => match type_arg(&f.ty) {
+                Some(ty) => FieldKind::Optional(ty),
+                None => {
+                    return Err(Error::new_spanned(
+                        &f.ty,
+                        "`builder(optional)` is only supported on types with a single \
+                         type parameter, like Option<T> or an alias of it",
+                    ))
+                }
+            },
+            None => {
+                if let Some(item) = std_type(&f.ty, &["std", "alloc"], "vec", "Vec") {
+                    FieldKind::Repeated {
+                        ty: &f.ty,
+                        item: Item::Single(item),
+                    }
+                } else if let Some(ty) = std_type(&f.ty, &["std", "core"], "option", "Option") {
+                    FieldKind::Optional(ty)
+                } else if let (Some(_), Some(item)) = (&attrs.each, collection_item(&f.ty)) {
+                    FieldKind::Repeated { ty: &f.ty, item }
+                } else {
+                    FieldKind::Required(&f.ty)
+                }
+            }
+        };
+
+        if attrs.each.is_some() && !matches!(kind, FieldKind::Repeated { .. }) {
+            return Err(Error::new_spanned(
+                &f.ty,
+                "`builder(each = \"...\")` is only supported on collections like Vec<T>, \
+                 HashSet<T> or HashMap<K, V>",
+            ));
+        }
+
+        let setter = match (&attrs.setter.name, attrs.setter.prefix(setter_defaults)) {
+            (Some(name), _) => name.clone(),
+            (None, Some(prefix)) => format_ident!("{}{}", prefix, ident),
+            (None, None) => ident.clone(),
+        };
+        let docs = f
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("doc"))
+            .collect();
+
+        results.push(BuilderField {
+            ident,
+            setter,
+            docs,
+            member,
+            ty: &f.ty,
+            kind,
+            attrs,
+            span: f.span(),
+        });
+    }
+
+    let (fields, skipped): (Vec<_>, Vec<_>) = results.into_iter().partition(|f| !f.attrs.skip);
+    check_method_names(&fields, struct_attrs, methods)?;
+    Ok((fields, skipped))
+}
+
+/// Fails if two methods of the builder would get the same name, e.g. the
+/// setter of a field `name_ref` and the accessor of a field `name`. The error
+/// points at the field whose method comes last.
+fn check_method_names(
+    fields: &[BuilderField],
+    struct_attrs: &StructAttrs,
+    own: &[&str],
+) -> Result<()> {
+    let mut methods: Vec<(String, String, Span)> = ["build", "is_complete", "missing_fields"]
+        .iter()
+        .chain(struct_attrs.serde.then_some(&"merge"))
+        .chain(own)
+        .map(|name| (name.to_string(), format!("`{}()`", name), Span::call_site()))
+        .collect();
+    for f in fields {
+        let ident = &f.ident;
+        let mut add = |name: String, what: &str| {
+            methods.push((name, format!("{} `{}`", what, ident), f.span));
+        };
+
+        let each = f.attrs.each.as_ref();
+        if each != Some(&f.setter.to_string()) {
+            add(f.setter.to_string(), "the setter of");
+        }
+        if let Some(each) = each {
+            add(each.clone(), "the `each` setter of");
+        }
+        if f.attrs.try_setter || struct_attrs.try_setter {
+            add(format!("try_{}", f.setter), "the `try_` setter of");
+        }
+        if !matches!(f.kind, FieldKind::SubBuilder(_)) {
+            add(format!("{}_ref", ident), "the `_ref` accessor of");
+        }
+        let required = matches!(f.kind, FieldKind::Required(_))
+            && f.attrs.default.is_none()
+            && !struct_attrs.default;
+        if !(required && struct_attrs.typestate) {
+            add(format!("clear_{}", ident), "the `clear_` method of");
+        }
+    }
+
+    for (i, (name, what, span)) in methods.iter().enumerate() {
+        if let Some((_, first, _)) = methods[..i].iter().find(|(other, ..)| other == name) {
+            return Err(Error::new(
+                *span,
+                format!(
+                    "the builder method `{}` would be generated twice, as {} and as {}",
+                    name, first, what
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn builder_field(target: &Target) -> TokenStream {
+    let recurse = target.fields.iter().map(|f| {
+        let ident = &f.ident;
+        match f.kind {
+            FieldKind::Repeated { ty, .. } if f.collection_default().is_none() => {
+                quote_spanned! {f.span=>
+                    #ident : #ty
+                }
+            }
+            FieldKind::Repeated { ty, .. } | FieldKind::Optional(ty) | FieldKind::Required(ty) => {
+                quote_spanned! {f.span=>
+                    #ident : ::core::option::Option<#ty>
+                }
+            }
+            FieldKind::SubBuilder(ref builder) => quote_spanned! {f.span=>
+                #ident : #builder
+            },
+        }
+    });
+
+    let marker = target.marker_field();
+
+    quote! {
+        #(#recurse,)*
+        #marker
+    }
+}
+
+fn builder_setter(target: &Target, pattern: Pattern) -> TokenStream {
+    let recurse = target
+        .fields
+        .iter()
+        .map(|f| field_setter(f, target, pattern));
+
+    quote! {
+        #(#recurse)*
+    }
+}
+
+fn field_setter(f: &BuilderField, target: &Target, pattern: Pattern) -> TokenStream {
+    let ident = &f.ident;
+    let setter = &f.setter;
+    let docs = &f.docs;
+    let setter_defaults = &target.attrs.setter;
+    let into = f.attrs.setter.accepts_into(setter_defaults);
+    let with_try = f.attrs.try_setter || target.attrs.try_setter;
+
+    // `target` is the builder being modified and returned by the setter.
+    let (receiver, ret, target, prologue) = match pattern {
+        Pattern::Mutable => (quote!(&mut self), quote!(&mut Self), quote!(self), None),
+        Pattern::Owned => (quote!(mut self), quote!(Self), quote!(self), None),
+        Pattern::Immutable => {
+            let idents = target.fields.iter().map(|f| &f.ident);
+            let marker = target.marker_init();
+            let prologue = quote! {
+                let mut __builder = Self {
+                    #(#idents: ::core::clone::Clone::clone(&self.#idents),)*
+                    #marker
+                };
+            };
+            (
+                quote!(&self),
+                quote!(Self),
+                quote!(__builder),
+                Some(prologue),
+            )
+        }
+    };
+
+    let setters = match f.kind {
+        // Nested builders are changed in place rather than replaced.
+        FieldKind::SubBuilder(ref builder) => quote_spanned! {f.span=>
+            #(#docs)*
+            pub fn #setter(&mut self) -> &mut #builder {
+                &mut self.#ident
             }
-            _ => Err(Error::new_spanned(ident, "only support named field")),
         },
-        _ => Err(Error::new_spanned(ident, "only support struct")),
-    }
-}
-fn builder_setter(data: &Data, ident: &Ident) -> Result<TokenStream> {
-    match *data {
-        Data::Struct(ref data) => match data.fields {
-            Fields::Named(ref fields) => {
-                let recurse = fields.named.iter().map(|f| {
-                    let ident = match f.ident.as_ref() {
-                        Some(ident) => ident,
-                        None => return Error::new_spanned(&f.ident, "anyonymous filed is not support")
-                                .into_compile_error()
-                    };
-                    if let Some(vec_component_ty) = is_type(&f.ty, "Vec") {
-                        match builder_each_attr(f) {
-                            Ok(Some(builder_name)) => {
-                                if ident == &builder_name {
-                                    quote_spanned! {f.span()=>
-                                        pub fn #ident(&mut self, val: #vec_component_ty) -> &mut Self {
-                                            self.#ident.push(val);
-                                            self
-                                        }
-                                    }
-                                } else {
-                                    let builder_ident = format_ident!("{}", builder_name);
-                                    quote_spanned! {f.span()=>
-                                        pub fn #ident(&mut self, val: std::vec::Vec<#vec_component_ty>) -> &mut Self {
-                                            self.#ident = val;
-                                            self
-                                        }
-
-                                        pub fn #builder_ident(&mut self, val: #vec_component_ty) -> &mut Self {
-                                            self.#ident.push(val);
-                                            self
-                                        }
-                                    }
-                                }
-                            },
-                            Ok(None) => quote_spanned! {f.span()=>
-                                pub fn #ident(&mut self, val: Vec<#vec_component_ty>) -> &mut Self {
-                                    self.#ident = val;
-                                    self
-                                }
-                            },
-                            Err(e) => Error::into_compile_error(e),
-                        }
-                    } else {
-                        let file_type = match is_type(&f.ty, "Option") {
-                            Some(ty) => ty,
-                            None => &f.ty,
-                        };
-                        quote_spanned! {f.span()=>
-                            pub fn #ident(&mut self, val: #file_type) -> &mut Self {
-                                self.#ident = std::option::Option::Some(val);
-                                self
-                            }
-                        }
+        FieldKind::Repeated { ty, ref item } => {
+            let (value, items) = match f.collection_default() {
+                Some(default) => (
+                    quote!(::core::option::Option::Some),
+                    quote! {
+                        ::core::option::Option::get_or_insert_with(
+                            &mut #target.#ident,
+                            || #default,
+                        )
+                    },
+                ),
+                None => (quote!(), quote!(&mut #target.#ident)),
+            };
+
+            // The all-at-once setter would clash with an `each` setter of the
+            // same name.
+            let all = if f.attrs.each.as_ref().is_some_and(|each| setter == each) {
+                None
+            } else {
+                let (generics, ty, val) = setter_arg(quote!(#ty), into);
+                Some(quote_spanned! {f.span=>
+                    #(#docs)*
+                    pub fn #setter #generics(#receiver, val: #ty) -> #ret {
+                        #prologue
+                        #target.#ident = #value(#val);
+                        #target
                     }
-                });
-                Ok(quote! {
-                    #(#recurse)*
                 })
+            };
+
+            let each = f.attrs.each.as_ref().map(|each| {
+                let each = format_ident!("{}", each);
+                let (generics, args, item) = match *item {
+                    Item::Single(item) => {
+                        let (generics, ty, val) = setter_arg(quote!(#item), into);
+                        (generics, quote!(val: #ty), val)
+                    }
+                    Item::Entry(key, value) if into => (
+                        Some(quote! {
+                            <__K: ::core::convert::Into<#key>, __V: ::core::convert::Into<#value>>
+                        }),
+                        quote!(key: __K, value: __V),
+                        quote! {
+                            (::core::convert::Into::into(key), ::core::convert::Into::into(value))
+                        },
+                    ),
+                    Item::Entry(key, value) => {
+                        (None, quote!(key: #key, value: #value), quote!((key, value)))
+                    }
+                };
+                quote_spanned! {f.span=>
+                    #(#docs)*
+                    pub fn #each #generics(#receiver, #args) -> #ret {
+                        #prologue
+                        ::core::iter::Extend::extend(#items, ::core::iter::once(#item));
+                        #target
+                    }
+                }
+            });
+
+            quote! {
+                #all
+                #each
             }
-            _ => Err(Error::new_spanned(ident, "only named filed support")),
-        },
-        _ => Err(Error::new_spanned(ident, "onply support struct")),
+        }
+        FieldKind::Optional(file_type) if !f.attrs.setter.strip_option(setter_defaults) => {
+            let (generics, ty, val) = setter_arg(quote!(::core::option::Option<#file_type>), into);
+            quote_spanned! {f.span=>
+                #(#docs)*
+                pub fn #setter #generics(#receiver, val: #ty) -> #ret {
+                    #prologue
+                    #target.#ident = #val;
+                    #target
+                }
+            }
+        }
+        FieldKind::Optional(file_type) | FieldKind::Required(file_type) => {
+            let (generics, ty, val) = setter_arg(quote!(#file_type), into);
+            quote_spanned! {f.span=>
+                #(#docs)*
+                pub fn #setter #generics(#receiver, val: #ty) -> #ret {
+                    #prologue
+                    #target.#ident = ::core::option::Option::Some(#val);
+                    #target
+                }
+            }
+        }
+    };
+
+    let try_setter = if with_try {
+        setter_value_ty(f, setter_defaults).map(|ty| try_setter(f, &receiver, &ret, &ty))
+    } else {
+        None
+    };
+
+    quote! {
+        #setters
+        #try_setter
     }
 }
 
-fn builder_each_attr(f: &syn::Field) -> Result<Option<String>> {
-    match is_type(&f.ty, "Vec") {
-        Some(_) => {}
-        None => return Ok(None),
-    };
+/// What the setter named after the field takes, before `setter(into)`. `None`
+/// when an `each` setter took its name.
+fn setter_value_ty(f: &BuilderField, setter_defaults: &SetterAttrs) -> Option<TokenStream> {
+    match f.kind {
+        FieldKind::Repeated { .. }
+            if f.attrs.each.as_ref().is_some_and(|each| f.setter == each) =>
+        {
+            None
+        }
+        FieldKind::Repeated { ty, .. } => Some(quote!(#ty)),
+        FieldKind::Optional(ty) if !f.attrs.setter.strip_option(setter_defaults) => {
+            Some(quote!(::core::option::Option<#ty>))
+        }
+        FieldKind::Optional(ty) | FieldKind::Required(ty) => Some(quote!(#ty)),
+        FieldKind::SubBuilder(_) => None,
+    }
+}
+
+/// `try_field`, which converts its argument with `TryInto` and passes it on to
+/// the regular setter.
+fn try_setter(
+    f: &BuilderField,
+    receiver: &TokenStream,
+    ret: &TokenStream,
+    ty: &TokenStream,
+) -> TokenStream {
+    let setter = &f.setter;
+    let try_setter = format_ident!("try_{}", setter);
+    let docs = &f.docs;
+    quote_spanned! {f.span=>
+        #(#docs)*
+        pub fn #try_setter<__V: ::core::convert::TryInto<#ty>>(
+            #receiver,
+            val: __V,
+        ) -> ::core::result::Result<#ret, __V::Error> {
+            let val: #ty = ::core::convert::TryInto::try_into(val)?;
+            ::core::result::Result::Ok(self.#setter(val))
+        }
+    }
+}
+
+/// Generics, argument type and stored value of a setter for `ty`. With `into`
+/// the setter accepts anything that converts into `ty`.
+fn setter_arg(ty: TokenStream, into: bool) -> (Option<TokenStream>, TokenStream, TokenStream) {
+    if into {
+        (
+            Some(quote!(<__V: ::core::convert::Into<#ty>>)),
+            quote!(__V),
+            quote!(::core::convert::Into::into(val)),
+        )
+    } else {
+        (None, ty, quote!(val))
+    }
+}
+
+/// The keys of `#[builder(setter(...))]`, given on the struct or on a field.
+fn setter_attrs(
+    key: &str,
+    meta: ParseNestedMeta,
+    options: &Options,
+    setter: &mut SetterAttrs,
+) -> Result<()> {
+    match key {
+        "into" => setter.into = Some(options.bool(&meta)?),
+        "strip_option" => setter.strip_option = Some(options.bool(&meta)?),
+        "name" => setter.name = Some(options.string(&meta)?.parse()?),
+        "prefix" => setter.prefix = Some(options.string(&meta)?.value()),
+        _ => unreachable!(),
+    }
+    Ok(())
+}
 
-    let mut name = None;
+fn builder_field_attrs(f: &syn::Field) -> Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
+    let mut options = Options::new("builder", FIELD_KEYS).misplaced(STRUCT_KEYS, "on the struct");
+    let mut setter_options = Options::new("setter", SETTER_KEYS);
+    let mut sub_builder_options = Options::new("sub_builder", &["builder"]);
     for attr in &f.attrs {
         if !attr.path().is_ident("builder") {
             continue;
         }
 
-        if name.is_some() {
-            return Err(Error::new_spanned(&f.ident, "too much builder attrs"));
-        }
-
-        let nested = attr.parse_args_with(Punctuated::<Meta, Token![=]>::parse_terminated);
-
-        match nested {
-            Ok(nested) => {
-                for meta in &nested {
-                    match meta {
-                        Meta::NameValue(name_value) => {
-                            if !name_value.path.is_ident("each") {
-                                return Err(syn::Error::new_spanned(
-                                    &attr.meta,
-                                    "expected `builder(each = \"...\")`",
-                                ));
-                            }
-
-                            match &name_value.value {
-                                Expr::Lit(ExprLit { lit, .. }) => {
-                                    if let Lit::Str(str) = lit {
-                                        name = Some(str.value());
-                                    } else {
-                                        return Err(syn::Error::new_spanned(
-                                            &attr.meta,
-                                            "expected `builder(each = \"...\")`",
-                                        ));
-                                    }
-                                }
-                                _ => {
-                                    return Err(syn::Error::new_spanned(
-                                        &attr.meta,
-                                        "expected `builder(each = \"...\")`",
-                                    ))
-                                }
-                            }
-                        }
-                        _ => {
-                            return Err(syn::Error::new_spanned(
-                                meta.path().get_ident(),
-                                "Unknow meta",
-                            ));
+        attr.parse_nested_meta(|meta| {
+            match options.key(&meta)? {
+                "each" => field_attrs.each = Some(options.string(&meta)?.value()),
+                "default" => {
+                    field_attrs.default = Some(match options.optional_string(&meta)? {
+                        Some(lit) => FieldDefault::Expr(lit.parse()?),
+                        None => FieldDefault::Trait,
+                    })
+                }
+                "name" => field_attrs.name = Some(options.string(&meta)?.parse()?),
+                "skip" => field_attrs.skip = options.bool(&meta)?,
+                "try_setter" => field_attrs.try_setter = options.bool(&meta)?,
+                "sub_builder" if meta.input.peek(token::Paren) => {
+                    field_attrs.sub_builder = true;
+                    options.list(&meta, |meta| match sub_builder_options.key(&meta)? {
+                        "builder" => {
+                            let builder = sub_builder_options.string(&meta)?.parse()?;
+                            field_attrs.sub_builder_ty = Some(builder);
+                            Ok(())
                         }
-                    }
+                        _ => unreachable!(),
+                    })?;
                 }
+                "sub_builder" => field_attrs.sub_builder = options.bool(&meta)?,
+                "optional" => {
+                    options.flag(&meta)?;
+                    field_attrs.kind = Some(KindOverride::Optional);
+                }
+                "required" => {
+                    options.flag(&meta)?;
+                    field_attrs.kind = Some(KindOverride::Required);
+                }
+                "setter" => options.list(&meta, |meta| match setter_options.key(&meta)? {
+                    // `setter(skip)` is the same as `skip`.
+                    "skip" => {
+                        setter_options.flag(&meta)?;
+                        field_attrs.skip = true;
+                        Ok(())
+                    }
+                    key => setter_attrs(key, meta, &setter_options, &mut field_attrs.setter),
+                })?,
+                _ => unreachable!(),
             }
-            Err(err) => {
-                return Err(Error::new_spanned(
-                    &f.ident,
-                    format!("get filed attrs error:{}", err),
-                ));
+            Ok(())
+        })?;
+    }
+
+    // `skip` and `setter(skip)` leave nothing for the other options to apply to.
+    let skip = options
+        .seen("skip")
+        .map(|path| ("builder", path))
+        .or(setter_options.seen("skip").map(|path| ("setter", path)));
+    options.conflict("optional", "required")?;
+    for other in ["each", "sub_builder", "try_setter", "optional", "required"] {
+        attrs::conflict(skip, options.seen(other).map(|path| ("builder", path)))?;
+    }
+    for setter in ["into", "strip_option", "name", "prefix"] {
+        attrs::conflict(
+            skip,
+            setter_options.seen(setter).map(|path| ("setter", path)),
+        )?;
+    }
+    for other in ["each", "default", "optional", "required", "try_setter"] {
+        options.conflict("sub_builder", other)?;
+    }
+
+    Ok(field_attrs)
+}
+
+/// The expression `build()` uses for a field, or `None` when a missing value
+/// has to be reported as an error.
+///
+/// When `owned` is set the builder is consumed and the value is moved out of it
+/// instead of being cloned.
+fn field_value(f: &BuilderField, target: &Target, owned: bool) -> Option<TokenStream> {
+    if let FieldKind::SubBuilder(_) = f.kind {
+        return Some(sub_builder_value(f, target, owned));
+    }
+
+    let struct_attrs = target.attrs;
+    let member = &f.member;
+    let source = field_source(&f.ident, owned);
+    let fallback = match &f.attrs.default {
+        Some(FieldDefault::Trait) => Some(quote!(::core::default::Default::default())),
+        Some(FieldDefault::Expr(expr)) => Some(quote!(#expr)),
+        None if struct_attrs.default => Some(quote!(__default.#member)),
+        None => None,
+    };
+
+    let value = match (&f.kind, fallback) {
+        (FieldKind::Repeated { .. }, Some(fallback)) if f.collection_default().is_some() => {
+            quote_spanned! {f.span=>
+                ::core::option::Option::unwrap_or_else(#source, || #fallback)
+            }
+        }
+        (FieldKind::Repeated { .. }, _) | (FieldKind::Optional(_), None) => {
+            quote_spanned! {f.span=>
+                #source
+            }
+        }
+        (FieldKind::Optional(_), Some(fallback)) => quote_spanned! {f.span=>
+            ::core::option::Option::or_else(#source, || #fallback)
+        },
+        (FieldKind::Required(_), Some(fallback)) => quote_spanned! {f.span=>
+            ::core::option::Option::unwrap_or_else(#source, || #fallback)
+        },
+        (FieldKind::Required(_), None) => return None,
+        (FieldKind::SubBuilder(_), _) => unreachable!(),
+    };
+
+    Some(value)
+}
+
+/// Builds a nested builder, reporting its missing fields under a dotted path
+/// like `server.port`.
+fn sub_builder_value(f: &BuilderField, target: &Target, owned: bool) -> TokenStream {
+    let ident = &f.ident;
+    let error_name = &target.error_name;
+    let alloc = target.attrs.alloc();
+    let name = ident.to_string();
+    let built = sub_builder_build(f, target, owned);
+
+    quote_spanned! {f.span=>
+        match #built {
+            ::core::result::Result::Ok(__value) => __value,
+            ::core::result::Result::Err(__error) => {
+                let __missing = __error.missing_fields();
+                return ::core::result::Result::Err(if #alloc::vec::Vec::is_empty(&__missing) {
+                    #error_name::Validation(#alloc::format!("{}: {}", #name, __error))
+                } else {
+                    #error_name::MissingNestedFields(
+                        ::core::iter::Iterator::collect(::core::iter::Iterator::map(
+                            ::core::iter::IntoIterator::into_iter(__missing),
+                            |__path| #alloc::format!("{}.{}", #name, __path),
+                        )),
+                    )
+                });
             }
         }
     }
+}
 
-    Ok(name)
+/// The result of building a nested builder, which an owned builder gives up
+/// and any other borrows the way it is borrowed itself.
+fn sub_builder_build(f: &BuilderField, target: &Target, owned: bool) -> TokenStream {
+    let ident = &f.ident;
+    match target.attrs.pattern() {
+        _ if owned => quote_spanned!(f.span=> self.#ident.__build_owned()),
+        Pattern::Immutable => quote_spanned!(f.span=> self.#ident.__build_ref()),
+        Pattern::Mutable | Pattern::Owned => quote_spanned!(f.span=> self.#ident.__build_mut()),
+    }
 }
 
-fn builder_fn(data: &Data, ident: &Ident) -> Result<TokenStream> {
-    match *data {
-        Data::Struct(ref data) => match data.fields {
-            Fields::Named(ref fields) => {
-                let field_extract = fields.named.iter().map(|f| {
-                    let ident = match f.ident.as_ref() {
-                        Some(ident) => ident,
-                        None => {
-                            return Error::new_spanned(ident, "anyonymous filed is not support")
-                                .into_compile_error()
-                        }
-                    };
+/// Reads a field out of the builder, cloning it unless the builder is owned.
+fn field_source(ident: &Ident, owned: bool) -> TokenStream {
+    if owned {
+        quote!(self.#ident)
+    } else {
+        quote!(::core::clone::Clone::clone(&self.#ident))
+    }
+}
 
-                    if is_type(&f.ty, "Option").is_some() || is_type(&f.ty, "Vec").is_some() {
-                        quote_spanned! {f.span()=>
-                             #ident: self.#ident.clone()
-                        }
-                    } else {
-                        let msg = format!("missing {}", ident);
-                        quote_spanned! {f.span()=>
-                             #ident: self.#ident.clone().ok_or_else(|| #msg )?
+/// Wraps the constructed struct in `Ok`, running the `validate` hook first.
+fn build_result(built: TokenStream, struct_attrs: &StructAttrs, error_name: &Ident) -> TokenStream {
+    match &struct_attrs.validate {
+        Some(validate) => quote! {{
+            let __built = #built;
+            ::core::result::Result::map_err(#validate(&__built), #error_name::Validation)?;
+            ::core::result::Result::Ok(__built)
+        }},
+        None => quote! {
+            ::core::result::Result::Ok(#built)
+        },
+    }
+}
+
+/// The receiver of `build()`, and whether it owns the builder.
+fn build_receiver(pattern: Pattern) -> (TokenStream, bool) {
+    match pattern {
+        Pattern::Mutable => (quote!(&mut self), false),
+        Pattern::Owned => (quote!(self), true),
+        Pattern::Immutable => (quote!(&self), false),
+    }
+}
+
+fn builder_fn(target: &Target, ty_generics: &TypeGenerics) -> TokenStream {
+    let ident = target.ident;
+    let path = &target.path;
+    let error_name = &target.error_name;
+    let struct_attrs = target.attrs;
+
+    let struct_default = if struct_attrs.default {
+        Some(quote! {
+            let __default: #ident #ty_generics = ::core::default::Default::default();
+        })
+    } else {
+        None
+    };
+
+    let (receiver, owned) = build_receiver(struct_attrs.pattern());
+
+    if struct_attrs.collect_errors {
+        return builder_fn_collect_errors(target, ty_generics, struct_default);
+    }
+
+    let field_extract = target.fields.iter().map(|f| {
+        let ident = &f.ident;
+        let member = &f.member;
+        match field_value(f, target, owned) {
+            Some(value) => quote_spanned! {f.span=>
+                #member: #value
+            },
+            None => {
+                let name = ident.to_string();
+                let source = field_source(ident, owned);
+                quote_spanned! {f.span=>
+                    #member: ::core::option::Option::ok_or(
+                        #source,
+                        #error_name::MissingField(#name),
+                    )?
+                }
+            }
+        }
+    });
+    let skipped = target.skipped_init();
+
+    let built = build_result(
+        quote! {
+            #path {
+                #(#field_extract,)*
+                #(#skipped,)*
+            }
+        },
+        struct_attrs,
+        error_name,
+    );
+
+    quote! {
+        pub fn build(#receiver) -> ::core::result::Result<#ident #ty_generics, #error_name> {
+            #struct_default
+            #built
+        }
+    }
+}
+
+// Instead of returning on the first unset field, match all required fields and
+// nested builds at once and only fall back to collecting the names of the
+// missing ones. Fields missing in nested builders are reported next to the
+// outer ones, under their dotted paths.
+fn builder_fn_collect_errors(
+    target: &Target,
+    ty_generics: &TypeGenerics,
+    struct_default: Option<TokenStream>,
+) -> TokenStream {
+    let ident = target.ident;
+    let path = &target.path;
+    let error_name = &target.error_name;
+    let struct_attrs = target.attrs;
+    let alloc = struct_attrs.alloc();
+    let (receiver, owned) = build_receiver(struct_attrs.pattern());
+    let nested = target
+        .fields
+        .iter()
+        .any(|f| matches!(f.kind, FieldKind::SubBuilder(_)));
+    let required = target.fields.iter().any(|f| {
+        !matches!(f.kind, FieldKind::SubBuilder(_)) && field_value(f, target, owned).is_none()
+    });
+    // Only needed to pick the variant when both kinds can be missing.
+    let has_nested = (nested && required).then(|| quote!(__has_nested = true;));
+
+    let mut field_extract = vec![];
+    let mut sources = vec![];
+    let mut patterns = vec![];
+    let mut bindings = vec![];
+    let mut checks = vec![];
+    for f in &target.fields {
+        let ident = &f.ident;
+        let member = &f.member;
+        let name = ident.to_string();
+        let check = match f.kind {
+            FieldKind::SubBuilder(_) => {
+                sources.push(sub_builder_build(f, target, owned));
+                patterns.push(quote!(::core::result::Result::Ok(#ident)));
+                quote_spanned! {f.span=>
+                    if let ::core::result::Result::Err(__error) = &#ident {
+                        let __nested = __error.missing_fields();
+                        if #alloc::vec::Vec::is_empty(&__nested) {
+                            return ::core::result::Result::Err(#error_name::Validation(
+                                #alloc::format!("{}: {}", #name, __error),
+                            ));
                         }
+                        #has_nested
+                        ::core::iter::Extend::extend(
+                            &mut __paths,
+                            ::core::iter::Iterator::map(
+                                ::core::iter::IntoIterator::into_iter(__nested),
+                                |__path| #alloc::format!("{}.{}", #name, __path),
+                            ),
+                        );
                     }
-                });
-                Ok(quote! {
-                    pub fn build(&mut self) -> std::result::Result<#ident, std::boxed::Box<dyn Error>> {
-                        use std::error::Error;
-                        Ok(#ident {
-                            #(#field_extract,)*
-                        })
+                }
+            }
+            _ => match field_value(f, target, owned) {
+                Some(value) => {
+                    field_extract.push(quote_spanned! {f.span=>
+                        #member: #value
+                    });
+                    continue;
+                }
+                None => {
+                    sources.push(field_source(ident, owned));
+                    patterns.push(quote!(::core::option::Option::Some(#ident)));
+                    let path = nested.then(|| {
+                        quote! {
+                            #alloc::vec::Vec::push(&mut __paths, #alloc::string::ToString::to_string(#name));
+                        }
+                    });
+                    quote_spanned! {f.span=>
+                        if ::core::option::Option::is_none(&#ident) {
+                            #alloc::vec::Vec::push(&mut __missing, #name);
+                            #path
+                        }
                     }
-                })
+                }
+            },
+        };
+        field_extract.push(quote_spanned! {f.span=>
+            #member: #ident
+        });
+        bindings.push(ident);
+        checks.push(check);
+    }
+
+    let skipped = target.skipped_init();
+    let built = build_result(
+        quote! {
+            #path {
+                #(#field_extract,)*
+                #(#skipped,)*
             }
-            _ => Err(Error::new_spanned(ident, "anyonymous filed is not support")),
         },
-        _ => Err(Error::new_spanned(ident, "only struct support")),
+        struct_attrs,
+        error_name,
+    );
+
+    let missing_arm = if bindings.is_empty() {
+        None
+    } else {
+        let (init, error) = match (nested, required) {
+            (false, _) => (
+                quote!(let mut __missing = #alloc::vec::Vec::new();),
+                quote!(#error_name::MissingFields(__missing)),
+            ),
+            (true, false) => (
+                quote!(let mut __paths = #alloc::vec::Vec::new();),
+                quote!(#error_name::MissingNestedFields(__paths)),
+            ),
+            (true, true) => (
+                quote! {
+                    let mut __missing = #alloc::vec::Vec::new();
+                    let mut __paths = #alloc::vec::Vec::new();
+                    let mut __has_nested = false;
+                },
+                quote! {
+                    if __has_nested {
+                        #error_name::MissingNestedFields(__paths)
+                    } else {
+                        #error_name::MissingFields(__missing)
+                    }
+                },
+            ),
+        };
+        Some(quote! {
+            (#(#bindings,)*) => {
+                #init
+                #(#checks)*
+                ::core::result::Result::Err(#error)
+            }
+        })
+    };
+
+    quote! {
+        pub fn build(#receiver) -> ::core::result::Result<#ident #ty_generics, #error_name> {
+            #struct_default
+            match (#(#sources,)*) {
+                (#(#patterns,)*) => #built,
+                #missing_arm
+            }
+        }
     }
 }
 
-fn builder_field_default(data: &Data, ident: &Ident) -> Result<TokenStream> {
-    match *data {
-        Data::Struct(ref data) => match data.fields {
-            Fields::Named(ref fields) => {
-                let recurse = fields.named.iter().map(|f| {
-                    let ident = match f.ident.as_ref() {
-                        Some(ident) => ident,
-                        None => {
-                            return Error::new_spanned(ident, "anyonymous filed is not support")
-                                .into_compile_error()
-                        }
-                    };
+fn builder_error(target: &Target) -> TokenStream {
+    let error_name = &target.error_name;
+    let vis = &target.vis;
+    let alloc = target.attrs.alloc();
+    quote! {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #[allow(dead_code)]
+        #vis enum #error_name {
+            MissingField(&'static str),
+            MissingFields(#alloc::vec::Vec<&'static str>),
+            /// Fields of `sub_builder` fields, as dotted paths like `server.port`.
+            MissingNestedFields(#alloc::vec::Vec<#alloc::string::String>),
+            Validation(#alloc::string::String),
+        }
 
-                    if is_type(&f.ty, "Vec").is_some() {
-                        quote_spanned! {f.span()=>
-                            #ident : std::vec::Vec::new()
+        impl #error_name {
+            /// Every field that was not set, empty for validation errors.
+            #vis fn missing_fields(&self) -> #alloc::vec::Vec<#alloc::string::String> {
+                match self {
+                    #error_name::MissingField(field) => {
+                        #alloc::vec![#alloc::string::ToString::to_string(field)]
+                    }
+                    #error_name::MissingFields(fields) => {
+                        ::core::iter::Iterator::collect(::core::iter::Iterator::map(
+                            <[&'static str]>::iter(fields),
+                            #alloc::string::ToString::to_string,
+                        ))
+                    }
+                    #error_name::MissingNestedFields(paths) => ::core::clone::Clone::clone(paths),
+                    #error_name::Validation(_) => #alloc::vec::Vec::new(),
+                }
+            }
+        }
+
+        // Inherent impls stay outside so diagnostics name `XBuilder::build`
+        // rather than `_::<impl XBuilder>::build`.
+        const _: () = {
+            impl ::core::fmt::Display for #error_name {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    match self {
+                        #error_name::MissingField(field) => ::core::write!(f, "missing {}", field),
+                        #error_name::MissingFields(fields) => {
+                            ::core::write!(f, "missing {}", <[&'static str]>::join(fields, ", "))
                         }
-                    } else {
-                        quote_spanned! {f.span()=>
-                            #ident : std::option::Option::None
+                        #error_name::MissingNestedFields(paths) => {
+                            ::core::write!(f, "missing {}", <[#alloc::string::String]>::join(paths, ", "))
                         }
+                        #error_name::Validation(msg) => ::core::write!(f, "{}", msg),
                     }
-                });
-                Ok(quote! {
-                    #(#recurse),*
-                })
+                }
             }
-            _ => Err(Error::new_spanned(ident, "anyonymous filed is not support")),
+
+            impl ::core::error::Error for #error_name {}
+        };
+    }
+}
+
+fn builder_field_default(target: &Target) -> TokenStream {
+    let recurse = target.fields.iter().map(|f| {
+        let ident = &f.ident;
+        let value = field_init(f);
+        quote_spanned! {f.span=>
+            #ident : #value
+        }
+    });
+
+    let marker = target.marker_init();
+
+    quote! {
+        #(#recurse,)*
+        #marker
+    }
+}
+
+/// The value a field starts out with in a new builder.
+fn field_init(f: &BuilderField) -> TokenStream {
+    match (&f.kind, f.collection_default()) {
+        (FieldKind::Repeated { .. }, None) => quote_spanned! {f.span=>
+            ::core::default::Default::default()
+        },
+        (FieldKind::SubBuilder(_), _) => {
+            let ty = f.ty;
+            quote_spanned! {f.span=>
+                <#ty>::builder()
+            }
+        }
+        _ => quote_spanned! {f.span=>
+            ::core::option::Option::None
         },
-        _ => Err(Error::new_spanned(ident, "only struct support")),
     }
 }
 
-fn is_type<'a>(ty: &'a Type, ty_str: &str) -> Option<&'a Type> {
-    let segments = if let Type::Path(TypePath {
-        path: Path { segments, .. },
-        ..
-    }) = ty
-    {
-        segments
-    } else {
-        return None;
+// Collections are told apart by their number of type arguments: one for the
+// item, two for the key and value of a map. The name only decides for a set
+// with a hasher, `HashSet<T, S>`, and a map with one, `HashMap<K, V, S>`.
+fn collection_item(ty: &Type) -> Option<Item<'_>> {
+    let seg = match ty {
+        Type::Path(TypePath { path, .. }) => path.segments.last()?,
+        _ => return None,
     };
 
-    let seg = match segments.last() {
-        Some(seg) => seg,
-        None => return None,
+    let args = match &seg.arguments {
+        PathArguments::AngleBracketed(AngleBracketedGenericArguments { args, .. }) => args,
+        _ => return None,
     };
 
-    if seg.ident != ty_str {
+    let types: Vec<&Type> = args
+        .iter()
+        .filter_map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .collect();
+
+    let name = seg.ident.to_string();
+    match types[..] {
+        [item] => Some(Item::Single(item)),
+        [item, _] if name.ends_with("Set") => Some(Item::Single(item)),
+        [key, value] => Some(Item::Entry(key, value)),
+        [key, value, _] if name.ends_with("Map") => Some(Item::Entry(key, value)),
+        _ => None,
+    }
+}
+
+fn snake_case(ident: &Ident) -> String {
+    let mut snake = String::new();
+    for (i, ch) in ident.to_string().chars().enumerate() {
+        if ch.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
+
+/// The builder of a `sub_builder` field's type, `a::Server<T>` gives
+/// `a::ServerBuilder<T>`.
+fn sub_builder_ty(ty: &Type) -> Result<Type> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => {
+            let mut builder = path.clone();
+            let last = builder.segments.last_mut().unwrap();
+            last.ident = format_ident!("{}Builder", last.ident);
+            Ok(parse_quote!(#builder))
+        }
+        _ => Err(Error::new_spanned(
+            ty,
+            "`builder(sub_builder)` is only supported on fields of a struct type \
+             that derives Builder, other builders are given with \
+             `builder(sub_builder(builder = \"...\"))`",
+        )),
+    }
+}
+
+/// The type argument of `ty` if it is the std type `name`, either written bare
+/// or as `module::name` under one of `crates`, e.g. `::std::option::Option<T>`.
+/// Paths into any other module name a different type.
+fn std_type<'a>(ty: &'a Type, crates: &[&str], module: &str, name: &str) -> Option<&'a Type> {
+    let path = match ty {
+        Type::Path(TypePath { qself: None, path }) => path,
+        _ => return None,
+    };
+
+    let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+    let matches = match segments.as_slice() {
+        [ty] => path.leading_colon.is_none() && ty == name,
+        [krate, m, ty] => crates.contains(&krate.as_str()) && m == module && ty == name,
+        _ => false,
+    };
+    if !matches {
         return None;
     }
 
+    type_arg(ty)
+}
+
+/// The single type argument of a path type, `T` in `Foo<T>`.
+fn type_arg(ty: &Type) -> Option<&Type> {
+    let seg = match ty {
+        Type::Path(TypePath { path, .. }) => path.segments.last()?,
+        _ => return None,
+    };
+
     match &seg.arguments {
-        PathArguments::AngleBracketed(AngleBracketedGenericArguments { args, .. }) => {
-            if let Some(GenericArgument::Type(ty)) = args.first() {
-                Some(ty)
-            } else {
-                None
+        PathArguments::AngleBracketed(AngleBracketedGenericArguments { args, .. })
+            if args.len() == 1 =>
+        {
+            match args.first() {
+                Some(GenericArgument::Type(ty)) => Some(ty),
+                _ => None,
             }
         }
         _ => None,
@@ -0,0 +1,368 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{parse_quote, GenericParam, Generics};
+
+use crate::{
+    build_result, builder_error, builder_field, builder_field_default, builder_from,
+    builder_init_from, builder_inspect, field_setter, field_value, setter_arg, try_setter,
+    BuilderField, FieldKind, Pattern, Target,
+};
+
+// With `#[builder(typestate)]` every required field gets its own type parameter
+// on the builder which is either `Set` or `Unset`. Setters of required fields
+// move the builder into the next state, and `build()` carries a bound per field
+// whose `on_unimplemented` message names the field that was forgotten.
+pub fn builder_typestate(target: &Target, generics: &Generics) -> TokenStream {
+    let ident = target.ident;
+    let path = &target.path;
+    let fields = &target.fields;
+    let struct_attrs = target.attrs;
+    let builder_name = &target.builder_name;
+    let builder_fn_name = &target.builder_fn;
+    let vis = &target.vis;
+    let state_mod = format_ident!("__{}State", builder_name);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let required = required_fields(target);
+    let states: Vec<Ident> = (0..required.len())
+        .map(|i| format_ident!("__State{}", i))
+        .collect();
+
+    // Defaults on the user's parameters would have to trail the state ones.
+    let mut builder_generics = generics.clone();
+    for param in &mut builder_generics.params {
+        if let GenericParam::Type(type_param) = param {
+            type_param.eq_token = None;
+            type_param.default = None;
+        }
+    }
+    // Named without arguments, the builder is the one `builder()` starts with.
+    for state in &states {
+        builder_generics
+            .params
+            .push(parse_quote!(#state = #state_mod::Unset));
+    }
+    let (builder_impl_generics, builder_ty_generics, _) = builder_generics.split_for_impl();
+
+    let user_args = user_args(generics);
+
+    // Function builders are finished with `call()` instead.
+    let (finish, what) = if target.call.is_some() {
+        ("call", "argument")
+    } else {
+        ("build", "field")
+    };
+    let markers = required.iter().map(|f| {
+        let field = &f.ident;
+        let message = format!(
+            "`{}::{}()` requires the `{}` {} to be set",
+            builder_name, finish, field, what
+        );
+        let label = format!("call `.{}(...)` before `.{}()`", f.setter, finish);
+        quote! {
+            #[diagnostic::on_unimplemented(message = #message, label = #label)]
+            pub trait #field {}
+            impl #field for Set {}
+        }
+    });
+
+    let setters = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let position = required.iter().position(|r| r.ident == *ident);
+        match (&f.kind, position) {
+            (FieldKind::Required(field_ty), Some(position)) => {
+                let into = f.attrs.setter.accepts_into(&struct_attrs.setter);
+                let (generics, ty, val) = setter_arg(quote!(#field_ty), into);
+                let next_states = states.iter().enumerate().map(|(i, state)| {
+                    if i == position {
+                        quote!(#state_mod::Set)
+                    } else {
+                        quote!(#state)
+                    }
+                });
+                let next = quote!(#builder_name<#(#user_args,)* #(#next_states,)*>);
+                let try_setter = if f.attrs.try_setter || struct_attrs.try_setter {
+                    Some(try_setter(f, &quote!(self), &next, &quote!(#field_ty)))
+                } else {
+                    None
+                };
+                let others = fields
+                    .iter()
+                    .map(|f| &f.ident)
+                    .filter(|other| *other != ident);
+                let marker = target.marker_init();
+                let setter = &f.setter;
+                let docs = &f.docs;
+                quote_spanned! {f.span=>
+                    #(#docs)*
+                    pub fn #setter #generics(self, val: #ty) -> #next {
+                        #builder_name {
+                            __state: ::core::marker::PhantomData,
+                            #ident: ::core::option::Option::Some(#val),
+                            #(#others: self.#others,)*
+                            #marker
+                        }
+                    }
+
+                    #try_setter
+                }
+            }
+            _ => field_setter(f, target, Pattern::Owned),
+        }
+    });
+
+    let field_extract = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let member = &f.member;
+        match field_value(f, target, true) {
+            Some(value) => quote_spanned! {f.span=>
+                #member: #value
+            },
+            None => quote_spanned! {f.span=>
+                #member: match self.#ident {
+                    ::core::option::Option::Some(val) => val,
+                    ::core::option::Option::None => ::core::unreachable!(),
+                }
+            },
+        }
+    });
+    let skipped = target.skipped_init();
+
+    let struct_default = if struct_attrs.default {
+        Some(quote! {
+            let __default: #ident #ty_generics = ::core::default::Default::default();
+        })
+    } else {
+        None
+    };
+
+    // Only a validation hook can still make a typestate `build()` fail.
+    let (build_ty, built, builder_error) = match struct_attrs.validate {
+        Some(_) => {
+            let error_name = &target.error_name;
+            let built = build_result(
+                quote! {
+                    #path {
+                        #(#field_extract,)*
+                        #(#skipped,)*
+                    }
+                },
+                struct_attrs,
+                error_name,
+            );
+            (
+                quote!(::core::result::Result<#ident #ty_generics, #error_name>),
+                built,
+                Some(builder_error(target)),
+            )
+        }
+        None => (
+            quote!(#ident #ty_generics),
+            quote! {
+                #path {
+                    #(#field_extract,)*
+                    #(#skipped,)*
+                }
+            },
+            None,
+        ),
+    };
+
+    let required_idents: Vec<&Ident> = required.iter().map(|f| &f.ident).collect();
+    let unset_ty = builder_state_ty(target, generics, &[]);
+    let builder_from = builder_from(
+        target,
+        generics,
+        &builder_state_ty(target, generics, &required_idents),
+        Some(quote!(__state: ::core::marker::PhantomData,)),
+    );
+    let call = target.call.as_ref().map(|call| {
+        let sig = &call.sig;
+        let body = &call.body;
+        quote! {
+            #sig
+            where
+                #(#states: #state_mod::#required_idents,)*
+            {
+                let __args = self.build();
+                #body
+            }
+        }
+    });
+    let builder_inspect = builder_inspect(target);
+    let (nested_items, nested_methods) = builder_nested(target, generics, &build_ty).unzip();
+    let builder_attrs = target.builder_attrs(generics);
+    let builder_fields = builder_field(target);
+    let default_builder_init = builder_field_default(target);
+
+    quote! {
+        #[doc(hidden)]
+        #[allow(non_snake_case, non_camel_case_types)]
+        mod #state_mod {
+            #[derive(Clone, Copy, Debug, Default)]
+            pub struct Set;
+            #[derive(Clone, Copy, Debug, Default)]
+            pub struct Unset;
+
+            #(#markers)*
+
+            #nested_items
+        }
+
+        impl #impl_generics #ident #ty_generics #where_clause {
+            #vis fn #builder_fn_name() -> #unset_ty {
+                #builder_name {
+                    __state: ::core::marker::PhantomData,
+                    #default_builder_init
+                }
+            }
+        }
+
+        #builder_attrs
+        #vis struct #builder_name #builder_generics #where_clause {
+            __state: ::core::marker::PhantomData<(#(#states,)*)>,
+            #builder_fields
+        }
+
+        impl #builder_impl_generics #builder_name #builder_ty_generics #where_clause {
+            #(#setters)*
+
+            #builder_inspect
+
+            pub fn build(self) -> #build_ty
+            where
+                #(#states: #state_mod::#required_idents,)*
+            {
+                #struct_default
+                #built
+            }
+
+            #call
+
+            #nested_methods
+        }
+
+        #builder_from
+        #builder_error
+    }
+}
+
+/// The fields that have to be set before `build()`, one state parameter each.
+fn required_fields<'a>(target: &'a Target) -> Vec<&'a BuilderField<'a>> {
+    target
+        .fields
+        .iter()
+        .filter(|f| field_value(f, target, true).is_none())
+        .collect()
+}
+
+/// The arguments naming the user's own parameters, `T` for `T: Clone = ()`.
+fn user_args(generics: &Generics) -> Vec<TokenStream> {
+    generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Type(type_param) => {
+                let ident = &type_param.ident;
+                quote!(#ident)
+            }
+            GenericParam::Lifetime(lifetime) => {
+                let lifetime = &lifetime.lifetime;
+                quote!(#lifetime)
+            }
+            GenericParam::Const(const_param) => {
+                let ident = &const_param.ident;
+                quote!(#ident)
+            }
+        })
+        .collect()
+}
+
+/// The builder type with the required fields in `set` set, the others unset.
+pub fn builder_state_ty(target: &Target, generics: &Generics, set: &[&Ident]) -> TokenStream {
+    let builder_name = &target.builder_name;
+    let state_mod = format_ident!("__{}State", builder_name);
+    let user_args = user_args(generics);
+    let states = required_fields(target).into_iter().map(|f| {
+        if set.contains(&&f.ident) {
+            quote!(#state_mod::Set)
+        } else {
+            quote!(#state_mod::Unset)
+        }
+    });
+    quote!(#builder_name<#(#user_args,)* #(#states,)*>)
+}
+
+/// The hidden methods an outer builder would use for a `sub_builder` field.
+/// A typestate builder is only filled in by value, so it cannot be nested.
+/// Every way of building it fails at the field with a single error, while the
+/// others are there so that nothing else has to.
+fn builder_nested(
+    target: &Target,
+    generics: &Generics,
+    build_ty: &TokenStream,
+) -> Option<(TokenStream, TokenStream)> {
+    if target.variant || target.call.is_some() {
+        return None;
+    }
+
+    let ident = target.ident;
+    let alloc = target.attrs.alloc();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let state_mod = format_ident!("__{}State", target.builder_name);
+    let built = match target.attrs.validate {
+        Some(_) => quote!(#build_ty),
+        None => quote!(::core::result::Result<#build_ty, #state_mod::NotNested>),
+    };
+    let from_value = builder_init_from(
+        target,
+        &Some(quote!(__state: ::core::marker::PhantomData,)),
+        &|member| quote!(value.#member),
+    );
+    let fail = quote! {
+        where
+            for<'__b> Self: #state_mod::SubBuilder,
+        {
+            ::core::unreachable!()
+        }
+    };
+    let items = quote! {
+        #[diagnostic::on_unimplemented(
+            message = "`{Self}` uses `builder(typestate)` and cannot be used with `builder(sub_builder)`",
+            label = "nested here",
+        )]
+        pub trait SubBuilder {}
+
+        /// What building a nested typestate builder would fail with.
+        #[derive(Debug)]
+        pub struct NotNested;
+
+        impl NotNested {
+            pub fn missing_fields(&self) -> #alloc::vec::Vec<#alloc::string::String> {
+                #alloc::vec::Vec::new()
+            }
+        }
+
+        impl ::core::fmt::Display for NotNested {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                f.write_str("typestate builders cannot be nested")
+            }
+        }
+    };
+    let methods = quote! {
+        #[doc(hidden)]
+        pub fn __from_value(value: #ident #ty_generics) -> Self {
+            #from_value
+        }
+
+        #[doc(hidden)]
+        pub fn __build_ref(&self) -> #built #fail
+
+        #[doc(hidden)]
+        pub fn __build_mut(&mut self) -> #built #fail
+
+        #[doc(hidden)]
+        pub fn __build_owned(self) -> #built #fail
+    };
+    Some((items, methods))
+}
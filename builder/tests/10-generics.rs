@@ -0,0 +1,43 @@
+// The generated builder should carry over the generic parameters of the input
+// struct, including lifetimes, trait bounds and where-clauses.
+//
+// Use Generics::split_for_impl to get the pieces that belong on the impl
+// blocks, and emit the generics together with the where-clause on the builder
+// struct itself.
+//
+//
+// Resources:
+//
+//   - Splitting generics for use in an impl:
+//     https://docs.rs/syn/2.0/syn/struct.Generics.html#method.split_for_impl
+
+use derive_builder::Builder;
+use std::fmt::Debug;
+
+#[derive(Builder)]
+pub struct Foo<'a, T: Clone>
+where
+    T: Debug,
+{
+    name: &'a str,
+    value: T,
+    #[builder(each = "item")]
+    items: Vec<T>,
+    fallback: Option<T>,
+}
+
+fn main() {
+    let name = String::from("foo");
+    let foo = Foo::builder()
+        .name(&name)
+        .value(1u8)
+        .item(2)
+        .item(3)
+        .build()
+        .unwrap();
+
+    assert_eq!(foo.name, "foo");
+    assert_eq!(foo.value, 1);
+    assert_eq!(foo.items, vec![2, 3]);
+    assert_eq!(foo.fallback, None);
+}
@@ -0,0 +1,34 @@
+// Callers should be able to find out programmatically why build() failed
+// rather than parsing a boxed error message.
+//
+// Generate an error enum named after the builder, CommandBuilderError, with a
+// MissingField variant carrying the name of the unset field. The enum needs
+// Debug and Display impls so that it can implement std::error::Error and still
+// be used with `?` in functions returning Box<dyn Error>.
+
+use derive_builder::Builder;
+use std::error::Error;
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    args: Vec<String>,
+    current_dir: Option<String>,
+}
+
+fn build_boxed() -> Result<Command, Box<dyn Error>> {
+    Ok(Command::builder().build()?)
+}
+
+fn main() {
+    let err = Command::builder().build().err().unwrap();
+    assert_eq!(err, CommandBuilderError::MissingField("executable"));
+    assert_eq!(err.to_string(), "missing executable");
+
+    match err {
+        CommandBuilderError::MissingField(field) => assert_eq!(field, "executable"),
+        _ => unreachable!(),
+    }
+
+    assert!(build_boxed().is_err());
+}
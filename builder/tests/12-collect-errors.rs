@@ -0,0 +1,106 @@
+// By default build() returns as soon as it finds the first required field that
+// was never set. Users filling a large struct from a form would rather see
+// every problem at once.
+//
+// Accept a struct-level #[builder(collect_errors)] attribute. In this mode
+// build() checks every required field and reports all of the missing ones
+// together through a MissingFields variant of the generated error enum.
+//
+// Nested builders are built along with the rest. If any of them misses fields,
+// everything missing is reported through MissingNestedFields instead, the
+// nested fields under dotted paths next to the outer ones.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(collect_errors)]
+pub struct Command {
+    executable: String,
+    working_dir: String,
+    timeout: u64,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+}
+
+#[derive(Builder)]
+#[builder(collect_errors)]
+pub struct Flags {
+    verbose: Option<bool>,
+}
+
+#[derive(Builder, Debug)]
+#[builder(collect_errors)]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Builder, Debug)]
+#[builder(collect_errors)]
+pub struct Deployment {
+    name: String,
+    #[builder(sub_builder)]
+    server: Server,
+    replicas: u32,
+}
+
+#[derive(Builder, Debug)]
+#[builder(collect_errors)]
+pub struct Proxy {
+    #[builder(sub_builder)]
+    upstream: Server,
+    timeout: Option<u64>,
+}
+
+fn main() {
+    let err = Command::builder().timeout(5).build().err().unwrap();
+    assert_eq!(
+        err,
+        CommandBuilderError::MissingFields(vec!["executable", "working_dir"]),
+    );
+    assert_eq!(err.to_string(), "missing executable, working_dir");
+
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .working_dir("/".to_owned())
+        .timeout(5)
+        .arg("build".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.args, vec!["build"]);
+    assert_eq!(command.current_dir, None);
+
+    let flags = Flags::builder().build().unwrap();
+    assert_eq!(flags.verbose, None);
+
+    let mut builder = Deployment::builder();
+    builder.server().port(80);
+    let err = builder.build().unwrap_err();
+    assert_eq!(
+        err,
+        DeploymentBuilderError::MissingNestedFields(vec![
+            "name".to_owned(),
+            "server.host".to_owned(),
+            "replicas".to_owned(),
+        ]),
+    );
+    assert_eq!(err.to_string(), "missing name, server.host, replicas");
+
+    let mut builder = Deployment::builder();
+    builder.server().host("localhost".to_owned()).port(80);
+    let err = builder.build().unwrap_err();
+    assert_eq!(
+        err,
+        DeploymentBuilderError::MissingFields(vec!["name", "replicas"]),
+    );
+
+    builder.name("web".to_owned()).replicas(3);
+    let deployment = builder.build().unwrap();
+    assert_eq!(deployment.server.host, "localhost");
+    assert_eq!(deployment.replicas, 3);
+
+    let err = Proxy::builder().build().unwrap_err();
+    assert_eq!(err.missing_fields(), vec!["upstream.host", "upstream.port"]);
+}
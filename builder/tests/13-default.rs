@@ -0,0 +1,74 @@
+// Fields that are not Option can still be left out of the builder calls if
+// they have a sensible default.
+//
+// Support #[builder(default)] on a field to fall back to Default::default(),
+// #[builder(default = "...")] to fall back to an arbitrary expression, and
+// #[builder(default)] on the struct to take every unset field from the
+// struct's own Default impl.
+
+use derive_builder::Builder;
+
+fn default_timeout() -> u64 {
+    30
+}
+
+#[derive(Builder)]
+pub struct Command {
+    executable: String,
+    #[builder(default)]
+    working_dir: String,
+    #[builder(default = "default_timeout()")]
+    timeout: u64,
+    #[builder(default = "Some(\"/tmp\".to_owned())")]
+    current_dir: Option<String>,
+    #[builder(each = "arg", default = "vec![\"-v\".to_owned()]")]
+    args: Vec<String>,
+}
+
+#[derive(Builder)]
+#[builder(default)]
+pub struct Server {
+    host: String,
+    port: u16,
+    #[builder(default = "8")]
+    workers: usize,
+    tls: Option<bool>,
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Server {
+            host: "localhost".to_owned(),
+            port: 80,
+            workers: 1,
+            tls: Some(false),
+        }
+    }
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .arg("build".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.working_dir, "");
+    assert_eq!(command.timeout, 30);
+    assert_eq!(command.current_dir.as_deref(), Some("/tmp"));
+    assert_eq!(command.args, vec!["-v", "build"]);
+
+    let command = Command::builder()
+        .executable("cargo".to_owned())
+        .timeout(5)
+        .current_dir("/".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.timeout, 5);
+    assert_eq!(command.current_dir.as_deref(), Some("/"));
+
+    let server = Server::builder().port(8080).build().unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.workers, 8);
+    assert_eq!(server.tls, Some(false));
+}
@@ -0,0 +1,37 @@
+// Missing required fields are only discovered at runtime by build(). With
+// #[builder(typestate)] the builder instead tracks in its type which required
+// fields have been set, and build() can only be called once all of them are.
+//
+// Every required field gets a type parameter on the builder that is either
+// Set or Unset. Setters of required fields consume the builder and return it
+// in the next state; build() consumes the builder and returns the struct
+// directly since it can no longer fail.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Command<'a, T: Clone> {
+    executable: &'a str,
+    timeout: T,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    #[builder(default = "2")]
+    retries: u32,
+    current_dir: Option<String>,
+}
+
+fn main() {
+    let command = Command::builder()
+        .arg("build".to_owned())
+        .timeout(5u64)
+        .current_dir("/".to_owned())
+        .executable("cargo")
+        .build();
+
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.timeout, 5);
+    assert_eq!(command.args, vec!["build"]);
+    assert_eq!(command.retries, 2);
+    assert_eq!(command.current_dir.as_deref(), Some("/"));
+}
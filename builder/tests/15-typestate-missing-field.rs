@@ -0,0 +1,16 @@
+// Forgetting a required field of a typestate builder is a compile error that
+// names the field, rather than a runtime error from build().
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Command {
+    executable: String,
+    timeout: u64,
+    current_dir: Option<String>,
+}
+
+fn main() {
+    let _command = Command::builder().timeout(5).build();
+}
@@ -0,0 +1,59 @@
+// Setters take `&mut self` by default and build() clones every field out of
+// the builder. Let the caller pick a different shape with
+// #[builder(pattern = "...")]:
+//
+//   - "mutable":   fn field(&mut self, ..) -> &mut Self, build(&mut self)
+//   - "owned":     fn field(self, ..) -> Self, build(self) moves the fields
+//                  out, so they do not need to implement Clone
+//   - "immutable": fn field(&self, ..) -> Self, build(&self), every setter
+//                  returns a modified copy of the builder
+
+use derive_builder::Builder;
+
+// Deliberately not Clone.
+#[derive(Debug, PartialEq)]
+pub struct Handle(u32);
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Owned {
+    handle: Handle,
+    #[builder(each = "extra")]
+    extras: Vec<Handle>,
+    fallback: Option<Handle>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "immutable")]
+pub struct Immutable {
+    name: String,
+    #[builder(each = "tag")]
+    tags: Vec<String>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "mutable", collect_errors)]
+pub struct Mutable {
+    name: String,
+}
+
+fn main() {
+    let owned = Owned::builder()
+        .extra(Handle(2))
+        .handle(Handle(1))
+        .build()
+        .unwrap();
+    assert_eq!(owned.handle, Handle(1));
+    assert_eq!(owned.extras, vec![Handle(2)]);
+    assert_eq!(owned.fallback, None);
+
+    let base = Immutable::builder().name("base".to_owned());
+    let tagged = base.tag("a".to_owned());
+    assert_eq!(base.build().unwrap().tags, Vec::<String>::new());
+    assert_eq!(tagged.build().unwrap().tags, vec!["a"]);
+    assert_eq!(tagged.build().unwrap().name, "base");
+
+    let mut builder = Mutable::builder();
+    builder.name("mutable".to_owned());
+    assert_eq!(builder.build().unwrap().name, "mutable");
+}
@@ -0,0 +1,69 @@
+// Setters take exactly the field type, which forces `.to_string()` and
+// `Some(..)` at every call site.
+//
+// With #[builder(setter(into))] the setter becomes generic over any
+// `V: Into<T>`. Setters of Option<T> fields take the inner T by default;
+// #[builder(setter(strip_option = false))] makes them take the whole
+// Option<T> instead. Both may be given on the struct to apply to every field,
+// and a field's own setting takes precedence.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+    #[builder(setter(into = false))]
+    timeout: u64,
+    #[builder(setter(strip_option = false))]
+    env: Option<String>,
+}
+
+#[derive(Builder)]
+#[builder(setter(strip_option = false))]
+pub struct Flags {
+    verbose: Option<bool>,
+    #[builder(setter(strip_option))]
+    quiet: Option<bool>,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    #[builder(setter(into))]
+    host: String,
+}
+
+fn main() {
+    let command = Command::builder()
+        .executable("cargo")
+        .arg("build")
+        .arg(String::from("--release"))
+        .current_dir("/")
+        .timeout(5)
+        .env(None)
+        .build()
+        .unwrap();
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(command.args, vec!["build", "--release"]);
+    assert_eq!(command.current_dir.as_deref(), Some("/"));
+    assert_eq!(command.env, None);
+
+    let command = Command::builder()
+        .executable("cargo")
+        .timeout(5)
+        .env("RUST_LOG=debug".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(command.env.as_deref(), Some("RUST_LOG=debug"));
+
+    let flags = Flags::builder().verbose(Some(true)).quiet(false).build().unwrap();
+    assert_eq!(flags.verbose, Some(true));
+    assert_eq!(flags.quiet, Some(false));
+
+    let server = Server::builder().host("localhost").build();
+    assert_eq!(server.host, "localhost");
+}
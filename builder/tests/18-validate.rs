@@ -0,0 +1,59 @@
+// Some invariants span several fields and can only be checked once all of
+// them are known.
+//
+// Accept #[builder(validate = "path::to::fn")] on the struct. build() calls
+// the function with a reference to the finished struct and turns an
+// Err(String) into the Validation variant of the generated error enum. A
+// typestate builder cannot fail otherwise, so only with a validation hook
+// does its build() return a Result.
+
+use derive_builder::Builder;
+
+mod checks {
+    pub fn range(range: &super::Range) -> Result<(), String> {
+        if range.start <= range.end {
+            Ok(())
+        } else {
+            Err(format!("{} is after {}", range.start, range.end))
+        }
+    }
+}
+
+#[derive(Builder)]
+#[builder(validate = "checks::range")]
+pub struct Range {
+    start: u32,
+    end: u32,
+}
+
+fn non_empty(name: &Name) -> Result<(), String> {
+    if name.value.is_empty() {
+        Err("name is empty".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Builder)]
+#[builder(typestate, validate = "non_empty")]
+pub struct Name {
+    value: String,
+}
+
+fn main() {
+    let range = Range::builder().start(1).end(2).build().unwrap();
+    assert_eq!((range.start, range.end), (1, 2));
+
+    let err = Range::builder().start(3).end(2).build().err().unwrap();
+    assert_eq!(err, RangeBuilderError::Validation("3 is after 2".to_owned()));
+    assert_eq!(err.to_string(), "3 is after 2");
+
+    let err = Range::builder().start(3).build().err().unwrap();
+    assert_eq!(err, RangeBuilderError::MissingField("end"));
+
+    assert_eq!(Name::builder().value("x".to_owned()).build().unwrap().value, "x");
+    assert_eq!(
+        Name::builder().value(String::new()).build().err(),
+        Some(NameBuilderError::Validation("name is empty".to_owned())),
+    );
+}
@@ -0,0 +1,48 @@
+// Builders are not limited to structs with named fields.
+//
+// For a tuple struct the setters are named after the field index, `_0`, `_1`
+// and so on, unless a field is given a name with #[builder(name = "...")].
+//
+// For an enum every variant with fields gets a builder of its own, created by
+// an associated function named after the variant: Shape::circle_builder()
+// returns a ShapeCircleBuilder whose build() produces a Shape. Unit variants
+// have nothing to build and are skipped.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Point(i32, #[builder(name = "y")] i32, Option<String>);
+
+#[derive(Debug, PartialEq, Builder)]
+pub enum Shape<T: Clone> {
+    Circle {
+        radius: T,
+        label: Option<String>,
+    },
+    RoundedRect(T, T, #[builder(default)] u32),
+    Empty,
+}
+
+fn main() {
+    let point = Point::builder()._0(1).y(2).build().unwrap();
+    assert_eq!((point.0, point.1, point.2), (1, 2, None));
+
+    let err = Point::builder()._0(1).build().err().unwrap();
+    assert_eq!(err, PointBuilderError::MissingField("y"));
+
+    let circle = Shape::circle_builder().radius(1.5).build().unwrap();
+    assert_eq!(
+        circle,
+        Shape::Circle {
+            radius: 1.5,
+            label: None,
+        }
+    );
+
+    let rect = Shape::rounded_rect_builder()._0(2).build();
+    assert_eq!(rect, Err(ShapeRoundedRectBuilderError::MissingField("_1")));
+
+    let rect = Shape::rounded_rect_builder()._0(2)._1(3).build().unwrap();
+    assert_eq!(rect, Shape::RoundedRect(2, 3, 0));
+    assert_ne!(rect, Shape::Empty);
+}
@@ -0,0 +1,101 @@
+// #[builder(each = "...")] is not limited to Vec. Any collection whose item
+// type can be read off its generic arguments gets a one-at-a-time setter that
+// goes through Extend, so sets, VecDeque and user-defined collections
+// implementing Extend<T> + Default all work.
+//
+// Collections with two type arguments are maps, whatever their name, and their
+// setter takes the key and the value as two separate arguments. Those with one
+// type argument take single items. The name only matters once a hasher is
+// added: `HashSet<T, S>` still takes single items and `HashMap<K, V, S>`
+// entries.
+
+use derive_builder::Builder;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Stack<T>(Vec<T>);
+
+impl<T> Extend<T> for Stack<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.0.insert(0, item);
+        }
+    }
+}
+
+/// A map that does not say so in its name.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct Dict<K, V>(Vec<(K, V)>);
+
+impl<K, V> Extend<(K, V)> for Dict<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+/// Not a map despite its name.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct BitMap<T>(Vec<T>);
+
+impl<T> Extend<T> for BitMap<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+#[derive(Builder)]
+pub struct Request {
+    #[builder(each = "header")]
+    headers: HashMap<String, String>,
+    #[builder(each = "param", setter(into))]
+    params: BTreeMap<String, u32>,
+    #[builder(each = "tag")]
+    tags: HashSet<&'static str>,
+    #[builder(each = "flags")]
+    flags: BTreeSet<char>,
+    #[builder(each = "retry")]
+    retries: VecDeque<u8>,
+    #[builder(each = "frame")]
+    frames: Stack<u8>,
+    #[builder(each = "entry")]
+    entries: Dict<&'static str, u8>,
+    #[builder(each = "bit")]
+    bits: BitMap<bool>,
+    #[builder(each = "id")]
+    ids: HashSet<u32, RandomState>,
+    #[builder(each = "cookie")]
+    cookies: HashMap<String, String, RandomState>,
+}
+
+fn main() {
+    let request = Request::builder()
+        .header("Accept".to_owned(), "*/*".to_owned())
+        .header("Host".to_owned(), "localhost".to_owned())
+        .param("page", 2u8)
+        .tag("a")
+        .tag("a")
+        .flags('x')
+        .retry(1)
+        .retry(2)
+        .frame(1)
+        .frame(2)
+        .entry("a", 1)
+        .bit(true)
+        .id(7)
+        .cookie("session".to_owned(), "abc".to_owned())
+        .build()
+        .unwrap();
+
+    assert_eq!(request.headers.len(), 2);
+    assert_eq!(request.headers["Host"], "localhost");
+    assert_eq!(request.params["page"], 2);
+    assert_eq!(request.tags.len(), 1);
+    assert!(request.flags.contains(&'x'));
+    assert_eq!(request.retries, VecDeque::from(vec![1, 2]));
+    assert_eq!(request.frames, Stack(vec![2, 1]));
+    assert_eq!(request.entries, Dict(vec![("a", 1)]));
+    assert_eq!(request.bits, BitMap(vec![true]));
+    assert!(request.ids.contains(&7));
+    assert_eq!(request.cookies["session"], "abc");
+}
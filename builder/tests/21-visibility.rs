@@ -0,0 +1,49 @@
+// The builder, its error type and the function returning it should be usable
+// from outside the module that defines the struct.
+//
+// By default they take the visibility of the input struct. The struct may
+// override it with #[builder(vis = "...")], rename the builder with
+// #[builder(name = "...")], derive traits on it with
+// #[builder(derive(...))], and put arbitrary attributes on it with
+// #[builder_attr(...)].
+
+mod config {
+    use derive_builder::Builder;
+
+    #[derive(Builder)]
+    pub struct Server {
+        pub host: String,
+        pub port: u16,
+    }
+
+    #[derive(Builder)]
+    #[builder(vis = "pub(crate)", name = "ClientConfig", derive(Debug, Clone))]
+    #[builder_attr(must_use = "a builder does nothing until build() is called")]
+    pub struct Client {
+        pub endpoint: String,
+        pub retries: Option<u8>,
+    }
+}
+
+use config::{ClientConfig, ClientConfigError, ServerBuilder, ServerBuilderError};
+
+fn main() {
+    let mut builder: ServerBuilder = config::Server::builder();
+    builder.host("localhost".to_owned());
+    assert_eq!(
+        builder.build().err(),
+        Some(ServerBuilderError::MissingField("port")),
+    );
+
+    let server = builder.port(80).build().unwrap();
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.port, 80);
+
+    let mut client: ClientConfig = config::Client::builder();
+    client.endpoint("http://localhost".to_owned());
+    let copy = client.clone();
+    assert!(format!("{:?}", copy).contains("http://localhost"));
+
+    let err: Option<ClientConfigError> = ClientConfig::clone(&copy).retries(3).build().err();
+    assert!(err.is_none());
+}
@@ -0,0 +1,51 @@
+// The expansion should not leak any imports into the caller's module, so that
+// several builders can live side by side with the caller's own items even when
+// those shadow standard library names like `Error` or even `std` itself.
+
+mod app {
+    use derive_builder::Builder;
+
+    #[derive(Debug)]
+    pub struct Error;
+
+    #[allow(dead_code)]
+    mod std {}
+
+    #[derive(Builder)]
+    pub struct Command {
+        pub executable: String,
+    }
+
+    #[derive(Builder)]
+    pub struct Server {
+        pub host: String,
+        pub port: u16,
+    }
+
+    pub fn run() -> Result<(Command, Server), Error> {
+        let command = Command::builder()
+            .executable("cargo".to_owned())
+            .build()
+            .map_err(|_| Error)?;
+        let server = Server::builder()
+            .host("localhost".to_owned())
+            .port(8080)
+            .build()
+            .map_err(|_| Error)?;
+        Ok((command, server))
+    }
+}
+
+fn main() {
+    let (command, server) = app::run().ok().unwrap();
+    assert_eq!(command.executable, "cargo");
+    assert_eq!(server.host, "localhost");
+    assert_eq!(server.port, 8080);
+
+    let err = match app::Server::builder().port(1).build() {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err,
+    };
+    assert_eq!(err.to_string(), "missing host");
+    let _: &dyn ::std::error::Error = &err;
+}
@@ -0,0 +1,69 @@
+// Crates without the standard library should be able to use the derive too.
+//
+// Every path in the expansion goes through ::core, apart from Vec and String in
+// the error type, which come from ::std. With #[builder(no_std)] those are
+// taken from ::alloc instead, so a #![no_std] crate only needs
+// `extern crate alloc;`.
+//
+// The test still links std for the runtime, but under a different name, so
+// any leftover ::std path fails to resolve.
+
+#![no_std]
+
+extern crate alloc;
+extern crate std as _runtime;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use derive_builder::Builder;
+
+fn check(packet: &Packet) -> Result<(), String> {
+    if packet.payload.len() <= 4 {
+        Ok(())
+    } else {
+        Err("payload too long".to_string())
+    }
+}
+
+#[derive(Builder)]
+#[builder(no_std, collect_errors, validate = "check")]
+pub struct Packet {
+    id: u8,
+    port: u16,
+    #[builder(each = "byte")]
+    payload: Vec<u8>,
+    tag: Option<&'static str>,
+}
+
+#[derive(Builder)]
+#[builder(no_std, pattern = "owned", typestate)]
+pub struct Frame {
+    len: u16,
+    #[builder(default)]
+    flags: u8,
+}
+
+fn main() {
+    let packet = Packet::builder().id(1).port(80).byte(0).byte(1).build().ok().unwrap();
+    assert_eq!(packet.id, 1);
+    assert_eq!(packet.port, 80);
+    assert_eq!(packet.payload, [0, 1]);
+    assert_eq!(packet.tag, None);
+
+    let err = match Packet::builder().byte(0).build() {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err,
+    };
+    assert_eq!(err, PacketBuilderError::MissingFields(Vec::from(["id", "port"])));
+    assert_eq!(err.to_string(), "missing id, port");
+
+    let err = match Packet::builder().id(1).port(80).payload(Vec::from([0; 5])).build() {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err,
+    };
+    assert_eq!(err.to_string(), "payload too long");
+
+    let frame = Frame::builder().len(3).build();
+    assert_eq!(frame.len, 3);
+    assert_eq!(frame.flags, 0);
+}
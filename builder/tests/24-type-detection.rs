@@ -0,0 +1,58 @@
+// Optional and repeated fields are recognized by their path, which may be
+// written in full: std::option::Option<T>, ::core::option::Option<T>,
+// std::vec::Vec<T>, ::alloc::vec::Vec<T>. A type that merely happens to be
+// called Option or Vec in some other module is an ordinary required field.
+//
+// Aliases cannot be seen through by a macro, so a field can be marked as
+// #[builder(optional)] or #[builder(required)] to fix its classification.
+
+use derive_builder::Builder;
+
+mod my {
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Option<T>(pub T);
+}
+
+type Maybe<T> = Option<T>;
+
+#[derive(Builder)]
+pub struct Config {
+    name: std::option::Option<String>,
+    port: ::core::option::Option<u16>,
+    args: std::vec::Vec<String>,
+    wrapped: my::Option<u8>,
+    #[builder(optional)]
+    alias: Maybe<u32>,
+    #[builder(required)]
+    timeout: Option<u64>,
+}
+
+fn main() {
+    let config = Config::builder()
+        .port(8080)
+        .args(vec!["-v".to_owned()])
+        .wrapped(my::Option(1))
+        .timeout(None)
+        .build()
+        .unwrap();
+
+    assert_eq!(config.name, None);
+    assert_eq!(config.port, Some(8080));
+    assert_eq!(config.args, vec!["-v"]);
+    assert_eq!(config.wrapped, my::Option(1));
+    assert_eq!(config.alias, None);
+    assert_eq!(config.timeout, None);
+
+    // Without a value for the required fields build() fails.
+    assert!(Config::builder().timeout(Some(5)).build().is_err());
+    assert!(Config::builder().wrapped(my::Option(0)).build().is_err());
+
+    let config = Config::builder()
+        .wrapped(my::Option(2))
+        .alias(7)
+        .timeout(Some(30))
+        .build()
+        .unwrap();
+    assert_eq!(config.alias, Some(7));
+    assert_eq!(config.timeout, Some(30));
+}
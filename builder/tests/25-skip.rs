@@ -0,0 +1,55 @@
+// Some fields are computed rather than chosen by the caller, like caches or
+// timestamps, and should not get a setter at all.
+//
+// A field with #[builder(skip)] or #[builder(setter(skip))] has no setter and
+// no storage in the builder. build() fills it from its
+// #[builder(default = "...")] expression if there is one, from the struct's
+// Default impl under #[builder(default)], and from Default::default()
+// otherwise.
+
+use derive_builder::Builder;
+use std::collections::HashMap;
+
+fn now() -> u64 {
+    1_700_000_000
+}
+
+#[derive(Builder)]
+pub struct Request {
+    url: String,
+    #[builder(skip)]
+    cache: HashMap<String, String>,
+    #[builder(setter(skip), default = "now()")]
+    created_at: u64,
+}
+
+#[derive(Builder, Default)]
+#[builder(default, pattern = "owned", typestate)]
+pub struct Counter {
+    start: u32,
+    #[builder(skip)]
+    label: String,
+}
+
+#[derive(Builder)]
+pub enum Event {
+    Tick(u32, #[builder(skip)] Vec<u32>),
+}
+
+fn main() {
+    let request = Request::builder()
+        .url("https://example.com".to_owned())
+        .build()
+        .unwrap();
+    assert_eq!(request.url, "https://example.com");
+    assert!(request.cache.is_empty());
+    assert_eq!(request.created_at, 1_700_000_000);
+
+    let counter = Counter::builder().start(3).build();
+    assert_eq!(counter.start, 3);
+    assert_eq!(counter.label, "");
+
+    let Event::Tick(tick, history) = Event::tick_builder()._0(1).build().unwrap();
+    assert_eq!(tick, 1);
+    assert!(history.is_empty());
+}
@@ -0,0 +1,63 @@
+// Going back from a built value to its builder makes it easy to take an
+// existing config, tweak a couple of fields and build it again.
+//
+// Generate `impl From<T> for TBuilder` with every field set, as well as
+// `T::to_builder(&self)` which does the same by cloning each field. The
+// latter is only callable when all fields are Clone, so structs holding
+// something like a file handle still derive Builder fine.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug, PartialEq)]
+pub struct Config {
+    name: String,
+    port: u16,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    comment: Option<String>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned", typestate)]
+pub struct Point<T> {
+    x: T,
+    y: T,
+}
+
+pub struct Handle;
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct Connection {
+    handle: Handle,
+}
+
+fn main() {
+    let config = Config::builder()
+        .name("server".to_owned())
+        .port(80)
+        .arg("-v".to_owned())
+        .build()
+        .unwrap();
+
+    let changed = config.to_builder().port(8080).build().unwrap();
+    assert_eq!(changed.name, "server");
+    assert_eq!(changed.port, 8080);
+    assert_eq!(changed.args, vec!["-v"]);
+    assert_eq!(changed.comment, None);
+
+    let mut builder = ConfigBuilder::from(config);
+    builder.arg("-q".to_owned()).comment("rebuilt".to_owned());
+    let rebuilt = builder.build().unwrap();
+    assert_eq!(rebuilt.port, 80);
+    assert_eq!(rebuilt.args, vec!["-v", "-q"]);
+    assert_eq!(rebuilt.comment.as_deref(), Some("rebuilt"));
+
+    // A typestate builder made from a value has all its fields set already.
+    let point = Point::builder().x(1).y(2).build();
+    let moved = point.to_builder().y(5).build();
+    assert_eq!((moved.x, moved.y), (1, 5));
+
+    let connection = Connection::builder().handle(Handle).build().unwrap();
+    let _ = ConnectionBuilder::from(connection);
+}
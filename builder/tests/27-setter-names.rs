@@ -0,0 +1,52 @@
+// Setters are named after their field by default. A field can pick another
+// name with #[builder(setter(name = "..."))], and the struct can put a common
+// prefix in front of all of them with #[builder(setter(prefix = "with_"))].
+// A field's own prefix takes precedence over the struct's, its name over
+// both. The `each` setters keep the name they were given.
+//
+// Doc comments on a field are repeated on its setters, so that the builder's
+// rustdoc documents what each of them does.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(setter(prefix = "with_"))]
+pub struct Request {
+    /// The URL to send the request to.
+    url: String,
+    /// Seconds to wait for a response.
+    #[builder(setter(name = "timeout_secs"))]
+    timeout: u64,
+    #[builder(setter(prefix = "add_"), each = "header")]
+    headers: Vec<String>,
+    body: Option<String>,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned", typestate)]
+pub struct Point {
+    /// Horizontal position.
+    #[builder(setter(name = "at_x"))]
+    x: i32,
+    #[builder(setter(name = "at_y"))]
+    y: i32,
+}
+
+fn main() {
+    let request = Request::builder()
+        .with_url("https://example.com".to_owned())
+        .timeout_secs(30)
+        .header("Accept: */*".to_owned())
+        .add_headers(vec!["Host: example.com".to_owned()])
+        .with_body("{}".to_owned())
+        .build()
+        .unwrap();
+
+    assert_eq!(request.url, "https://example.com");
+    assert_eq!(request.timeout, 30);
+    assert_eq!(request.headers, vec!["Host: example.com"]);
+    assert_eq!(request.body.as_deref(), Some("{}"));
+
+    let point = Point::builder().at_x(1).at_y(2).build();
+    assert_eq!((point.x, point.y), (1, 2));
+}
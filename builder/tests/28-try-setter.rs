@@ -0,0 +1,61 @@
+// Values coming from strings or wider integers have to be converted before
+// they can be given to a setter, and that conversion may fail.
+//
+// With #[builder(try_setter)], on a field or on the whole struct, every setter
+// gets a `try_` twin taking any V: TryInto<T>. It returns the conversion error
+// instead of the builder when the value does not fit.
+
+use derive_builder::Builder;
+use std::net::IpAddr;
+use std::num::TryFromIntError;
+
+#[derive(Clone)]
+pub struct Host(String);
+
+impl TryFrom<&str> for Host {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if value.is_empty() {
+            Err("empty host".to_owned())
+        } else {
+            Ok(Host(value.to_owned()))
+        }
+    }
+}
+
+#[derive(Builder)]
+pub struct Server {
+    #[builder(try_setter)]
+    port: u16,
+    #[builder(try_setter)]
+    host: Option<Host>,
+    addr: Option<IpAddr>,
+}
+
+#[derive(Builder)]
+#[builder(try_setter, pattern = "owned", typestate)]
+pub struct Limits {
+    max: u8,
+    min: u8,
+}
+
+fn main() -> Result<(), TryFromIntError> {
+    let server = Server::builder()
+        .try_port(8080u64)?
+        .try_host("localhost")
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(server.host.unwrap().0, "localhost");
+    assert_eq!(server.addr, None);
+
+    assert!(Server::builder().try_port(70000u32).is_err());
+    assert!(Server::builder().try_host("").is_err());
+
+    let limits = Limits::builder().try_max(10i64)?.try_min(1u32)?.build();
+    assert_eq!((limits.min, limits.max), (1, 10));
+
+    Ok(())
+}
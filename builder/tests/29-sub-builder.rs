@@ -0,0 +1,158 @@
+// A field whose type derives Builder itself does not have to be built
+// separately and passed in.
+//
+// With #[builder(sub_builder)] the parent builder holds the field's builder
+// and hands it out by `&mut` from a method named after the field. The
+// parent's build() builds it as well. Fields missing in the nested builder are
+// reported with a dotted path, e.g. `server.port`, and are also available
+// through the error's missing_fields() method.
+//
+// The nested builder is the field type's name followed by `Builder`, which has
+// to be in scope next to the type. #[builder(sub_builder(builder = "..."))]
+// names it instead, for builders named otherwise and types written through an
+// alias. It may use any pattern. When the parent's build() only borrows it and
+// its own build() would need more than that, a clone of it is built, so it
+// then has to derive Clone.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug, Clone, PartialEq)]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+pub struct Config {
+    name: String,
+    #[builder(sub_builder)]
+    server: Server,
+}
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+pub struct App {
+    #[builder(sub_builder)]
+    config: Config,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(pattern = "owned", name = "Credentials", derive(Clone))]
+pub struct Login {
+    user: String,
+    #[builder(default)]
+    password: Option<String>,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+pub struct Session {
+    #[builder(sub_builder(builder = "Credentials"))]
+    login: Login,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(pattern = "immutable", derive(Clone))]
+pub struct Settings {
+    verbose: bool,
+}
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(pattern = "immutable", derive(Clone))]
+pub struct Tool {
+    #[builder(sub_builder)]
+    settings: Settings,
+    #[builder(sub_builder(builder = "Credentials"))]
+    login: Login,
+}
+
+mod net {
+    use derive_builder::Builder;
+
+    #[derive(Builder, Debug, Clone, PartialEq)]
+    pub struct Endpoint {
+        pub host: String,
+        pub port: u16,
+    }
+}
+
+use net::{Endpoint, EndpointBuilder};
+
+type Upstream = net::Endpoint;
+
+#[derive(Builder, Debug, PartialEq)]
+pub struct Route {
+    #[builder(sub_builder)]
+    from: Endpoint,
+    #[builder(sub_builder)]
+    via: net::Endpoint,
+    #[builder(sub_builder(builder = "net::EndpointBuilder"))]
+    to: Upstream,
+}
+
+fn main() {
+    let mut builder = Config::builder();
+    builder.name("prod".to_owned());
+    builder.server().host("localhost".to_owned()).port(80);
+    let config = builder.build().unwrap();
+    assert_eq!(config.name, "prod");
+    assert_eq!(
+        config.server,
+        Server {
+            host: "localhost".to_owned(),
+            port: 80,
+        },
+    );
+
+    let mut builder = Config::builder();
+    builder.name("prod".to_owned());
+    builder.server().host("localhost".to_owned());
+    let err = builder.build().unwrap_err();
+    assert_eq!(
+        err,
+        ConfigBuilderError::MissingNestedFields(vec!["server.port".to_owned()]),
+    );
+    assert_eq!(err.to_string(), "missing server.port");
+
+    // Paths grow with every level of nesting.
+    let mut builder = App::builder();
+    builder.config().name("prod".to_owned());
+    let err = match builder.build() {
+        Ok(_) => panic!("expected an error"),
+        Err(err) => err,
+    };
+    assert_eq!(err.missing_fields(), vec!["config.server.host"]);
+    assert_eq!(err.to_string(), "missing config.server.host");
+
+    // A value turned back into a builder carries its nested values along.
+    let mut builder = config.to_builder();
+    builder.server().port(8080);
+    assert_eq!(builder.build().unwrap().server.port, 8080);
+
+    // An owned builder under a name of its own is built from a clone.
+    let mut builder = Session::builder();
+    *builder.login() = builder.login().clone().user("admin".to_owned());
+    let session = builder.build().unwrap();
+    assert_eq!(session.login.user, "admin");
+    assert_eq!(builder.build().unwrap(), session);
+
+    // An immutable parent only borrows its nested builders.
+    let mut builder = Tool::builder();
+    *builder.settings() = builder.settings().verbose(true);
+    *builder.login() = builder.login().clone().user("root".to_owned());
+    let tool = builder.build().unwrap();
+    assert!(tool.settings.verbose);
+    assert_eq!(tool.login.user, "root");
+
+    // Nested structs may come from other modules along with their builders.
+    let mut builder = Route::builder();
+    builder.from().host("a".to_owned()).port(1);
+    builder.via().host("b".to_owned()).port(2);
+    builder.to().host("c".to_owned());
+    assert_eq!(builder.missing_fields(), vec!["to"]);
+    builder.to().port(3);
+    let route = builder.build().unwrap();
+    assert_eq!(route.from.host, "a");
+    assert_eq!(route.via.port, 2);
+    assert_eq!(route.to.host, "c");
+    assert_eq!(route.to_builder().build().unwrap(), route);
+}
@@ -0,0 +1,100 @@
+// With the `serde` feature, builders can double as partial configuration.
+// Under #[builder(serde)] they implement serde::Deserialize with every field
+// optional, so a file or an environment only has to mention what it changes.
+// merge() then layers one builder over another, taking the fields the other
+// one has set, extending collections and merging nested builders, before
+// build() checks that everything required is there.
+//
+// A collection with a default is only filled with it by build() if nothing was
+// set or added, so a deserialized builder that does not mention it builds
+// with the default on its own, and merging it does not repeat the default.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug, PartialEq)]
+#[builder(serde)]
+pub struct Database {
+    url: String,
+    pool_size: u32,
+    #[builder(each = "option", default = "vec![\"sslmode=prefer\".to_owned()]")]
+    options: Vec<String>,
+}
+
+#[derive(Builder, Debug)]
+#[builder(serde)]
+pub struct Config {
+    name: String,
+    port: u16,
+    #[builder(each = "feature")]
+    features: Vec<String>,
+    motd: Option<String>,
+    #[builder(sub_builder)]
+    database: Database,
+    #[builder(each = "arg", default = "vec![\"-v\".to_owned()]")]
+    args: Vec<String>,
+}
+
+fn main() {
+    let mut defaults = Config::builder();
+    defaults
+        .name("service".to_owned())
+        .port(80)
+        .feature("metrics".to_owned());
+    defaults.database().pool_size(4);
+
+    let file: ConfigBuilder = serde_json::from_str(
+        r#"{
+            "port": 8080,
+            "features": ["tracing"],
+            "database": { "url": "postgres://localhost" }
+        }"#,
+    )
+    .unwrap();
+
+    let env: ConfigBuilder = serde_json::from_str(r#"{ "motd": "hello" }"#).unwrap();
+
+    let config = defaults.merge(file).merge(env).build().unwrap();
+    assert_eq!(config.name, "service");
+    assert_eq!(config.port, 8080);
+    assert_eq!(config.features, vec!["metrics", "tracing"]);
+    assert_eq!(config.motd.as_deref(), Some("hello"));
+    assert_eq!(config.args, vec!["-v"]);
+    assert_eq!(
+        config.database,
+        Database {
+            url: "postgres://localhost".to_owned(),
+            pool_size: 4,
+            options: vec!["sslmode=prefer".to_owned()],
+        },
+    );
+
+    // Nothing but the input is required to deserialize, build() still is.
+    let mut partial: ConfigBuilder = serde_json::from_str("{}").unwrap();
+    assert_eq!(
+        partial.build().unwrap_err().to_string(),
+        "missing name",
+    );
+
+    let mut builder = Config::builder();
+    builder.name("service".to_owned()).port(80);
+    builder.database().url("postgres://localhost".to_owned()).pool_size(4);
+    let config = builder
+        .merge(serde_json::from_str("{}").unwrap())
+        .merge(serde_json::from_str(r#"{ "args": ["-q"] }"#).unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(config.args, vec!["-v", "-q"]);
+    assert_eq!(config.database.options, vec!["sslmode=prefer"]);
+
+    let mut builder: ConfigBuilder = serde_json::from_str(
+        r#"{
+            "name": "service",
+            "port": 80,
+            "database": { "url": "postgres://localhost", "pool_size": 4 }
+        }"#,
+    )
+    .unwrap();
+    let config = builder.build().unwrap();
+    assert_eq!(config.args, vec!["-v"]);
+    assert_eq!(config.database.options, vec!["sslmode=prefer"]);
+}
@@ -0,0 +1,102 @@
+// Functions with long parameter lists get named and optional arguments from
+// #[builder]. The function is replaced by one of the same name returning a
+// builder with a setter per argument, and call() runs the original body.
+//
+// Arguments take the same attributes as struct fields, and options that would
+// go on the struct go into the attribute itself. Methods need #[builder] on
+// their impl block as well, and their receiver is taken when the builder is
+// started.
+
+use derive_builder::builder;
+
+#[builder]
+fn connect(
+    host: String,
+    #[builder(default = "80")] port: u16,
+    #[builder(each = "option")] options: Vec<String>,
+) -> String {
+    format!("{}:{} {:?}", host, port, options)
+}
+
+#[builder(setter(into))]
+fn greet(name: String, greeting: Option<String>) -> String {
+    format!("{}, {}!", greeting.as_deref().unwrap_or("Hello"), name)
+}
+
+#[builder]
+fn largest<T: PartialOrd + Copy>(items: &[T], at_least: Option<T>) -> Option<T> {
+    items
+        .iter()
+        .copied()
+        .chain(at_least)
+        .fold(None, |max, item| match max {
+            Some(max) if max >= item => Some(max),
+            _ => Some(item),
+        })
+}
+
+#[builder]
+async fn fetch(url: &str, #[builder(default = "3")] retries: u32) -> String {
+    format!("{} ({} retries)", url, retries)
+}
+
+pub struct Counter {
+    count: u32,
+}
+
+#[builder]
+impl Counter {
+    #[builder]
+    pub fn new(#[builder(default)] start: u32) -> Self {
+        Counter { count: start }
+    }
+
+    #[builder]
+    pub fn add(&mut self, amount: u32, #[builder(default = "1")] times: u32) -> &mut Self {
+        self.count += amount * times;
+        self
+    }
+
+    #[builder]
+    pub fn describe(&self, #[builder(default)] prefix: Option<String>) -> String {
+        format!("{}{}", prefix.unwrap_or_default(), self.count)
+    }
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn main() {
+    let connection = connect()
+        .host("localhost".to_owned())
+        .option("tls".to_owned())
+        .option("gzip".to_owned())
+        .call();
+    assert_eq!(connection, "localhost:80 [\"tls\", \"gzip\"]");
+
+    assert_eq!(greet().name("world").call(), "Hello, world!");
+    assert_eq!(greet().greeting("Hi").name("you").call(), "Hi, you!");
+
+    assert_eq!(largest().items(&[3, 9, 4]).call(), Some(9));
+    assert_eq!(largest().items(&[3, 9, 4]).at_least(12).call(), Some(12));
+    assert_eq!(largest::<u8>().items(&[]).call(), None);
+
+    let fetched = block_on(fetch().url("example.com").call());
+    assert_eq!(fetched, "example.com (3 retries)");
+
+    let mut counter = Counter::new().start(2).call();
+    counter.add().amount(5).times(2).call().add().amount(1).call();
+    assert_eq!(counter.count, 13);
+    assert_eq!(counter.describe().call(), "13");
+    assert_eq!(counter.describe().prefix("count: ".to_owned()).call(), "count: 13");
+}
@@ -0,0 +1,13 @@
+// Arguments without a default have to be given before call(), and leaving one
+// out names it the same way a typestate builder names a missing field.
+
+use derive_builder::builder;
+
+#[builder]
+fn connect(host: String, #[builder(default = "80")] port: u16) -> String {
+    format!("{}:{}", host, port)
+}
+
+fn main() {
+    let _connection = connect().port(8080).call();
+}
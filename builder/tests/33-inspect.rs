@@ -0,0 +1,84 @@
+// Builders can be inspected while they are being filled in, e.g. to show
+// progress in a form before calling build().
+//
+// Every field gets an `x_ref()` accessor returning what has been set so far
+// and a `clear_x()` that unsets it again. `missing_fields()` lists the fields
+// build() would fail on and `is_complete()` tells whether there are none.
+// Typestate builders get the same, except that their required fields cannot
+// be cleared.
+
+use derive_builder::Builder;
+
+#[derive(Builder, Debug, Clone, PartialEq)]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Builder, Debug)]
+pub struct Form {
+    name: String,
+    email: String,
+    nickname: Option<String>,
+    #[builder(default = "18")]
+    age: u32,
+    #[builder(each = "tag")]
+    tags: Vec<String>,
+    #[builder(sub_builder)]
+    server: Server,
+}
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Command {
+    executable: String,
+    #[builder(each = "arg")]
+    args: Vec<String>,
+    current_dir: Option<String>,
+}
+
+fn main() {
+    let mut builder = Form::builder();
+    assert!(!builder.is_complete());
+    assert_eq!(builder.missing_fields(), ["name", "email", "server"]);
+    assert_eq!(builder.name_ref(), None);
+    assert_eq!(builder.tags_ref(), Some(&vec![]));
+
+    builder.name("Ferris".to_owned()).tag("crab".to_owned());
+    builder.server().host("localhost".to_owned()).port(8080);
+    assert_eq!(builder.name_ref(), Some(&"Ferris".to_owned()));
+    assert_eq!(builder.tags_ref(), Some(&vec!["crab".to_owned()]));
+    assert_eq!(builder.missing_fields(), ["email"]);
+
+    builder.email("ferris@example.com".to_owned());
+    assert!(builder.is_complete());
+    assert!(builder.missing_fields().is_empty());
+
+    builder.clear_name();
+    builder.clear_tags();
+    builder.server().clear_port();
+    assert_eq!(builder.name_ref(), None);
+    assert_eq!(builder.tags_ref(), Some(&vec![]));
+    assert_eq!(builder.missing_fields(), ["name", "server"]);
+
+    builder.nickname("crab".to_owned()).age(7);
+    assert_eq!(builder.age_ref(), Some(&7));
+    builder.clear_nickname();
+    builder.clear_age();
+    assert_eq!(builder.nickname_ref(), None);
+    assert_eq!(builder.age_ref(), None);
+
+    let builder = Command::builder().arg("--help".to_owned());
+    assert_eq!(builder.missing_fields(), ["executable"]);
+    let mut builder = builder
+        .executable("cargo".to_owned())
+        .current_dir("/".to_owned());
+    assert!(builder.is_complete());
+    assert_eq!(builder.executable_ref(), Some(&"cargo".to_owned()));
+    builder.clear_current_dir();
+    builder.clear_args();
+    let command = builder.build();
+    assert_eq!(command.executable, "cargo");
+    assert!(command.args.is_empty());
+    assert_eq!(command.current_dir, None);
+}
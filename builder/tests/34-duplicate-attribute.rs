@@ -0,0 +1,20 @@
+// Giving the same option twice is an error, even when both agree, rather than
+// the last one silently winning. Lists like `setter(...)` may be split up, but
+// each option inside them still counts once. This is a compile_fail test.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(pattern = "owned")]
+#[builder(pattern = "immutable")]
+pub struct Command {
+    executable: String,
+}
+
+#[derive(Builder)]
+pub struct Request {
+    #[builder(setter(into), default, setter(strip_option, into))]
+    url: String,
+}
+
+fn main() {}
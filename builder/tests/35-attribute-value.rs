@@ -0,0 +1,24 @@
+// Options given a value of the wrong kind name the form they expect. This is a
+// compile_fail test.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    #[builder(each = arg)]
+    args: Vec<String>,
+}
+
+#[derive(Builder)]
+pub struct Request {
+    #[builder(setter(into = "yes"))]
+    url: String,
+}
+
+#[derive(Builder)]
+pub struct Response {
+    #[builder(default = 200)]
+    status: u16,
+}
+
+fn main() {}
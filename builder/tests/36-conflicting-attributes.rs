@@ -0,0 +1,24 @@
+// Options that contradict each other are rejected, pointing at the one given
+// last. This is a compile_fail test.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct Command {
+    #[builder(optional, required)]
+    timeout: Option<u64>,
+}
+
+#[derive(Builder)]
+pub struct Request {
+    #[builder(setter(skip, into))]
+    url: String,
+}
+
+#[derive(Builder)]
+pub struct Response {
+    #[builder(sub_builder, default)]
+    body: Command,
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+// A nested builder has to be filled in step by step through `&mut`, which a
+// typestate builder cannot be, as every setter changes its type. Nesting one
+// is an error at the field.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+#[builder(typestate)]
+pub struct Server {
+    host: String,
+    port: u16,
+}
+
+#[derive(Builder)]
+pub struct Config {
+    #[builder(sub_builder)]
+    server: Server,
+}
+
+fn main() {}
@@ -0,0 +1,26 @@
+// Besides the setters, a builder has methods named after its fields, like
+// `name_ref` and `clear_name`, and a few of its own, like `build`. A field or
+// setter name that would give two of them the same name is rejected at the
+// field, naming both. This is a compile_fail test.
+
+use derive_builder::Builder;
+
+#[derive(Builder)]
+pub struct User {
+    name: String,
+    name_ref: String,
+}
+
+#[derive(Builder)]
+pub struct Job {
+    #[builder(setter(name = "is_complete"))]
+    done: bool,
+}
+
+#[derive(Builder)]
+pub struct Cache {
+    #[builder(each = "clear_entries")]
+    entries: Vec<String>,
+}
+
+fn main() {}
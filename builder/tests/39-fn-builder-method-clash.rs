@@ -0,0 +1,16 @@
+// Function arguments become setters, so an argument can't share its name with
+// one of the builder's own methods, such as call() or build().
+
+use derive_builder::builder;
+
+#[builder]
+fn schedule(task: String, call: u32) -> String {
+    format!("{} {}", task, call)
+}
+
+#[builder]
+fn retry(attempts: u32, build: bool) -> u32 {
+    if build { attempts } else { 0 }
+}
+
+fn main() {}
@@ -0,0 +1,27 @@
+// A function builder is always a typestate builder that is consumed by call(),
+// so options that pick another kind of builder are rejected on functions along
+// with the ones that only make sense for structs.
+
+use derive_builder::builder;
+
+#[builder(pattern = "mutable")]
+fn connect(host: String) -> String {
+    host
+}
+
+#[builder(typestate = false)]
+fn resolve(host: String) -> String {
+    host
+}
+
+#[builder(name = "ListenBuilder", collect_errors)]
+fn listen(port: u16) -> u16 {
+    port
+}
+
+#[builder(default)]
+fn bind(port: u16) -> u16 {
+    port
+}
+
+fn main() {}
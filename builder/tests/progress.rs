@@ -10,4 +10,34 @@ fn tests() {
     t.pass("tests/07-repeated-field.rs");
     t.compile_fail("tests/08-unrecognized-attribute.rs");
     t.pass("tests/09-redefined-prelude-types.rs");
+    t.pass("tests/10-generics.rs");
+    t.pass("tests/11-error-type.rs");
+    t.pass("tests/12-collect-errors.rs");
+    t.pass("tests/13-default.rs");
+    t.pass("tests/14-typestate.rs");
+    t.compile_fail("tests/15-typestate-missing-field.rs");
+    t.pass("tests/16-pattern.rs");
+    t.pass("tests/17-setter-into.rs");
+    t.pass("tests/18-validate.rs");
+    t.pass("tests/19-tuple-and-enum.rs");
+    t.pass("tests/20-each-collections.rs");
+    t.pass("tests/21-visibility.rs");
+    t.pass("tests/22-hygiene.rs");
+    t.pass("tests/23-no-std.rs");
+    t.pass("tests/24-type-detection.rs");
+    t.pass("tests/25-skip.rs");
+    t.pass("tests/26-to-builder.rs");
+    t.pass("tests/27-setter-names.rs");
+    t.pass("tests/28-try-setter.rs");
+    t.pass("tests/29-sub-builder.rs");
+    t.pass("tests/31-fn-builder.rs");
+    t.compile_fail("tests/32-fn-builder-missing-argument.rs");
+    t.pass("tests/33-inspect.rs");
+    t.compile_fail("tests/34-duplicate-attribute.rs");
+    t.compile_fail("tests/35-attribute-value.rs");
+    t.compile_fail("tests/36-conflicting-attributes.rs");
+    t.compile_fail("tests/37-sub-builder-typestate.rs");
+    t.compile_fail("tests/38-method-name-clash.rs");
+    t.compile_fail("tests/39-fn-builder-method-clash.rs");
+    t.compile_fail("tests/40-fn-builder-struct-options.rs");
 }
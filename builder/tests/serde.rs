@@ -0,0 +1,8 @@
+// Tests of `#[builder(serde)]`, which only run with the `serde` feature:
+//
+//     cargo test --features serde --test serde-tests
+#[test]
+fn tests() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/30-serde.rs");
+}
@@ -1,10 +1,10 @@
-use proc_macro2::{Ident, TokenStream};
-use quote::quote;
+use proc_macro2::{Ident, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
 use std::{result::Result, vec};
 use syn::{
-    parse_macro_input, parse_quote, AngleBracketedGenericArguments, Attribute, Data, DataStruct,
-    DeriveInput, Error, Fields, GenericArgument, GenericParam, Generics, Lit, MetaNameValue, Path,
-    PathArguments, Type, TypePath, WherePredicate,
+    parse_macro_input, parse_quote, token, AngleBracketedGenericArguments, Attribute, Data,
+    DeriveInput, Error, Field, Fields, GenericArgument, GenericParam, Generics, Index, Lit, LitStr,
+    Member, Path, PathArguments, Type, TypePath, WherePredicate,
 };
 
 #[proc_macro_derive(CustomDebug, attributes(debug))]
@@ -31,100 +31,215 @@ pub fn derive(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 //第一步，如何识别T在PhantomData里面使用了？
 fn debug_impl(data: DeriveInput) -> Result<TokenStream, Error> {
     let ident = &data.ident;
-    let struct_data = if let Data::Struct(st) = &data.data {
-        st
-    } else {
-        return Err(syn::Error::new_spanned(ident, "Expect struct"));
+    let ident_name = ident.to_string();
+    let container_attrs = container_attrs(&data.attrs)?;
+    let except = container_attrs.redact_all_except.as_deref();
+    let (body, all_fields) = match &data.data {
+        Data::Struct(struct_data) => {
+            let values = struct_data.fields.iter().zip(members(&struct_data.fields));
+            let values = values.map(|(f, member)| (f, quote!(&self.#member)));
+            let body = debug_fields(&ident_name, &struct_data.fields, values, except)?;
+            (body, struct_data.fields.iter().collect::<Vec<_>>())
+        }
+        // Every field of a variant is bound by reference as `__self_0`, `__self_1`, ...
+        Data::Enum(enum_data) => {
+            let mut arms = vec![];
+            for variant in &enum_data.variants {
+                let variant_ident = &variant.ident;
+                let bindings: Vec<Ident> = (0..variant.fields.len())
+                    .map(|i| format_ident!("__self_{}", i))
+                    .collect();
+                let members = members(&variant.fields);
+                let pattern = match variant.fields {
+                    Fields::Named(_) => quote!({ #(#members: #bindings),* }),
+                    Fields::Unnamed(_) => quote!((#(#bindings),*)),
+                    Fields::Unit => quote!(),
+                };
+                let values = variant
+                    .fields
+                    .iter()
+                    .zip(bindings.iter().map(|b| quote!(#b)));
+                let name = variant_ident.to_string();
+                let body = debug_fields(&name, &variant.fields, values, except)?;
+                arms.push(quote! {
+                    #ident::#variant_ident #pattern => #body,
+                });
+            }
+            let body = if enum_data.variants.is_empty() {
+                quote!(match *self {})
+            } else {
+                quote! {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            };
+            let fields = enum_data.variants.iter().flat_map(|v| &v.fields).collect();
+            (body, fields)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "CustomDebug does not support unions",
+            ))
+        }
     };
-    let fields = fields(struct_data);
 
-    //let phantom_types = type_bounds_handle(&struct_data);
-    let generics = add_trait_bounds(struct_data, data.generics, &data.attrs);
+    // A misspelled exception would otherwise redact the field silently.
+    for name in except.unwrap_or_default() {
+        if !all_fields.iter().any(|f| f.ident.as_ref() == Some(name)) {
+            return Err(Error::new_spanned(
+                name,
+                format!("no field named `{}`", name),
+            ));
+        }
+    }
+
+    // Skipped and redacted fields are never formatted with `Debug`.
+    let mut printed = vec![];
+    for f in all_fields {
+        let field_attrs = field_attrs(f)?;
+        if !field_attrs.skip && redaction(f, &field_attrs, except).is_none() {
+            printed.push(f);
+        }
+    }
+
+    //let phantom_types = type_bounds_handle(&all_fields);
+    let bound = container_attrs.bound.as_deref();
+    let generics = add_trait_bounds(&printed, data.generics, bound);
     let (impl_generics, ty_genrics, where_clause) = generics.split_for_impl();
 
-    let ident_name = ident.to_string();
     Ok(quote! {
         impl #impl_generics std::fmt::Debug for #ident #ty_genrics #where_clause {
             fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                fmt.debug_struct(#ident_name)
-                #(.#fields)*
-                .finish()
+                #body
             }
         }
     })
 }
 
-fn scape_hatch(attrs: &[Attribute]) -> Result<Option<String>, Error> {
-    let attr = match attrs.last() {
-        Some(attr) => attr,
-        None => return Ok(None),
-    };
+/// The `#[debug(...)]` attributes of the struct or enum itself.
+#[derive(Default)]
+struct ContainerAttrs {
+    /// `#[debug(bound = "...")]`, replacing the inferred bounds.
+    bound: Option<String>,
+    /// `#[debug(redact_all_except(...))]`, the only named fields printed as is.
+    redact_all_except: Option<Vec<Ident>>,
+}
 
-    let meta_list = match &attr.meta {
-        syn::Meta::List(list) => list,
-        _ => return Ok(None),
-    };
+fn container_attrs(attrs: &[Attribute]) -> Result<ContainerAttrs, Error> {
+    let mut container_attrs = ContainerAttrs::default();
+    for attr in attrs {
+        if !attr.path().is_ident("debug") {
+            continue;
+        }
 
-    match syn::parse2::<MetaNameValue>(meta_list.tokens.clone()) {
-        Ok(name_value) => {
-            if !name_value.path.is_ident("bound") {
-                return Err(syn::Error::new_spanned(
-                    name_value.path,
-                    "unknow attr name ",
-                ));
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("bound") {
+                let lit: LitStr = meta.value()?.parse()?;
+                container_attrs.bound = Some(lit.value());
+                Ok(())
+            } else if meta.path.is_ident("redact_all_except") {
+                let except = container_attrs
+                    .redact_all_except
+                    .get_or_insert_with(Vec::new);
+                meta.parse_nested_meta(|meta| {
+                    except.push(meta.path.require_ident()?.clone());
+                    Ok(())
+                })
+            } else {
+                Err(meta
+                    .error("expected `debug(bound = \"...\")` or `debug(redact_all_except(...))`"))
             }
+        })?;
+    }
 
-            let explit = match name_value.value {
-                syn::Expr::Lit(lit) => lit,
-                _ => return Ok(None),
-            };
+    Ok(container_attrs)
+}
 
-            match explit.lit {
-                Lit::Str(str) => Ok(Some(str.value())),
-                _ => Ok(None),
-            }
-        }
-        Err(e) => Err(syn::Error::new_spanned(
-            meta_list,
-            format!("parse bound attr error:{:?}", e),
-        )),
-    }
+/// How each field is accessed, by name or by index.
+fn members(fields: &Fields) -> Vec<Member> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect()
 }
 
-fn fields(struct_data: &DataStruct) -> Vec<TokenStream> {
-    match struct_data.fields {
-        Fields::Named(ref fields) => {
-            let mut results = vec![];
-            for f in &fields.named {
-                let ident = match f.ident.as_ref() {
-                    Some(ident) => ident,
-                    None => continue,
-                };
+/// Formats a struct or variant named `name` from references to its fields:
+/// `debug_struct` for named fields, `debug_tuple` for unnamed ones and just
+/// the name for a unit.
+///
+/// With `redact_all_except(...)` every field not listed is redacted, which
+/// includes all unnamed fields. Skipped fields are left out and marked by `..`.
+fn debug_fields<'a>(
+    name: &str,
+    fields: &Fields,
+    values: impl Iterator<Item = (&'a Field, TokenStream)>,
+    except: Option<&[Ident]>,
+) -> Result<TokenStream, Error> {
+    let mut calls = vec![];
+    let mut finish = quote!(finish);
+    for (f, value) in values {
+        let field_attrs = field_attrs(f)?;
+        if field_attrs.skip {
+            finish = quote!(finish_non_exhaustive);
+            continue;
+        }
 
+        let value = match (redaction(f, &field_attrs, except), field_attrs.format) {
+            (Some(Redact::Hidden), _) => quote!(&std::format_args!("<redacted>")),
+            (Some(Redact::Len), _) => {
+                quote!(&std::format_args!("<redacted, len {}>", (#value).len()))
+            }
+            (None, Some(str)) => quote!(&std::format_args!(#str, #value)),
+            (None, None) => value,
+        };
+        calls.push(match &f.ident {
+            Some(ident) => {
                 let ident_name = ident.to_string();
+                quote!(.field(#ident_name, #value))
+            }
+            None => quote!(.field(#value)),
+        });
+    }
 
-                let token_stream = match debug_fmt(f) {
-                    Ok(Some(str)) => quote!(
-                        field(#ident_name, &std::format_args!(#str, &self.#ident))
-                    ),
-                    Ok(_) => quote!(
-                        field(#ident_name, &self.#ident)
-                    ),
-                    Err(e) => e.into_compile_error(),
-                };
+    Ok(match fields {
+        Fields::Named(_) => quote! {
+            fmt.debug_struct(#name)
+            #(#calls)*
+            .#finish()
+        },
+        Fields::Unnamed(_) => quote! {
+            fmt.debug_tuple(#name)
+            #(#calls)*
+            .#finish()
+        },
+        Fields::Unit => quote!(fmt.write_str(#name)),
+    })
+}
 
-                results.push(token_stream);
-            }
-            results
-        }
-        _ => vec![],
+/// How a field is redacted, either by its own attribute or because it is
+/// missing from `redact_all_except(...)`.
+fn redaction(f: &Field, field_attrs: &FieldAttrs, except: Option<&[Ident]>) -> Option<Redact> {
+    let excepted = match (except, &f.ident) {
+        (Some(except), Some(ident)) => except.contains(ident),
+        (Some(_), None) => false,
+        (None, _) => true,
+    };
+    match field_attrs.redact {
+        None if !excepted => Some(Redact::Hidden),
+        redact => redact,
     }
 }
 
-fn type_bounds_handle(struct_data: &DataStruct) -> (Vec<Ident>, Vec<WherePredicate>) {
+fn type_bounds_handle(fields: &[&Field]) -> (Vec<Ident>, Vec<WherePredicate>) {
     let mut handled = vec![];
     let mut types = vec![];
-    for field in &struct_data.fields {
+    for field in fields {
         let ty = &field.ty;
         if let Some(ty) = is_type(ty, "PhantomData") {
             match ty {
@@ -187,50 +302,107 @@ fn type_bounds_handle(struct_data: &DataStruct) -> (Vec<Ident>, Vec<WherePredica
     (handled, types)
 }
 
-fn debug_fmt(f: &syn::Field) -> syn::Result<Option<String>> {
-    let name = None;
+/// The `#[debug...]` attributes of a field.
+#[derive(Default)]
+struct FieldAttrs {
+    /// `#[debug = "..."]`, a format string for the field's value.
+    format: Option<String>,
+    /// `#[debug(skip)]`, left out of the output.
+    skip: bool,
+    redact: Option<Redact>,
+}
+
+/// How a redacted field is printed instead of its value.
+#[derive(Clone, Copy)]
+enum Redact {
+    /// `#[debug(redact)]`, as `<redacted>`.
+    Hidden,
+    /// `#[debug(redact(len))]`, as `<redacted, len 12>` using the value's `len()`.
+    Len,
+}
+
+fn field_attrs(f: &Field) -> syn::Result<FieldAttrs> {
+    let mut field_attrs = FieldAttrs::default();
     for attr in &f.attrs {
         if !attr.path().is_ident("debug") {
             continue;
         }
 
-        if name.is_some() {
-            return Err(Error::new_spanned(&f.ident, "too much builder attrs"));
+        match &attr.meta {
+            syn::Meta::NameValue(name_value) => {
+                let str = match &name_value.value {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: Lit::Str(str), ..
+                    }) => str,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            &attr.meta,
+                            "expected `debug = \"...\"`",
+                        ))
+                    }
+                };
+                if field_attrs.format.is_some() {
+                    return Err(Error::new_spanned(
+                        &attr.meta,
+                        "duplicate `debug = \"...\"`",
+                    ));
+                }
+                field_attrs.format = Some(str.value());
+            }
+            syn::Meta::List(_) => attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("skip") {
+                    field_attrs.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("redact") {
+                    let mut redact = Redact::Hidden;
+                    if meta.input.peek(token::Paren) {
+                        meta.parse_nested_meta(|meta| {
+                            if meta.path.is_ident("len") {
+                                redact = Redact::Len;
+                                Ok(())
+                            } else {
+                                Err(meta.error("expected `debug(redact(len))`"))
+                            }
+                        })?;
+                    }
+                    field_attrs.redact = Some(redact);
+                    Ok(())
+                } else {
+                    Err(meta
+                        .error("expected `debug(skip)`, `debug(redact)` or `debug(redact(len))`"))
+                }
+            })?,
+            syn::Meta::Path(_) => {
+                return Err(syn::Error::new_spanned(
+                    &attr.meta,
+                    "expected `debug = \"...\"` or `debug(...)`",
+                ))
+            }
         }
+    }
 
-        return match &attr.meta {
-            syn::Meta::NameValue(name_value) => match &name_value.value {
-                syn::Expr::Lit(lit) => match &lit.lit {
-                    syn::Lit::Str(str) => Ok(Some(str.value())),
-                    _ => Err(syn::Error::new_spanned(
-                        &attr.meta,
-                        "expected `debug = \"...\"`",
-                    )),
-                },
-                _ => Err(syn::Error::new_spanned(
-                    attr.meta.path().get_ident(),
-                    "Unknow expr",
-                )),
-            },
-            _ => Err(syn::Error::new_spanned(
-                attr.meta.path().get_ident(),
-                "Unknow meta",
-            )),
-        };
+    if field_attrs.skip && (field_attrs.redact.is_some() || field_attrs.format.is_some()) {
+        return Err(Error::new_spanned(
+            f,
+            "a field with `debug(skip)` is not printed, so it cannot be redacted or formatted",
+        ));
     }
 
-    Ok(name)
+    if field_attrs.redact.is_some() && field_attrs.format.is_some() {
+        return Err(Error::new_spanned(
+            f,
+            "a field with `debug(redact)` cannot have a format as well",
+        ));
+    }
+
+    Ok(field_attrs)
 }
 
 // Add a bound `T: Debug` to every type parameter T.
 
-fn add_trait_bounds(
-    struct_data: &DataStruct,
-    mut generics: Generics,
-    attrs: &[Attribute],
-) -> Generics {
-    if let Ok(Some(lit)) = scape_hatch(attrs) {
-        match syn::parse_str(lit.to_string().as_str()) {
+fn add_trait_bounds(fields: &[&Field], mut generics: Generics, bound: Option<&str>) -> Generics {
+    if let Some(lit) = bound {
+        match syn::parse_str(lit) {
             Ok(where_predicated) => {
                 let where_clause = generics.make_where_clause();
                 where_clause.predicates.push(where_predicated);
@@ -238,7 +410,7 @@ fn add_trait_bounds(
             Err(err) => eprintln!("parse where_predicated error:{:?}", err),
         }
     } else {
-        let (handled, filed_type_bound) = type_bounds_handle(struct_data);
+        let (handled, filed_type_bound) = type_bounds_handle(fields);
         for param in &mut generics.params {
             let type_param = if let GenericParam::Type(ref mut type_param) = *param {
                 type_param
@@ -250,6 +422,14 @@ fn add_trait_bounds(
                 continue;
             }
 
+            // Parameters only used by fields that are not printed need no bound.
+            let used = fields
+                .iter()
+                .any(|f| mentions(f.ty.to_token_stream(), &type_param.ident));
+            if !used {
+                continue;
+            }
+
             type_param.bounds.push(parse_quote!(std::fmt::Debug));
         }
 
@@ -262,6 +442,15 @@ fn add_trait_bounds(
     generics
 }
 
+/// Whether `ident` appears anywhere in `tokens`.
+fn mentions(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(token) => token == *ident,
+        TokenTree::Group(group) => mentions(group.stream(), ident),
+        _ => false,
+    })
+}
+
 //应该使用全路径的。。。
 fn is_type<'a>(ty: &'a Type, ty_str: &str) -> Option<&'a Type> {
     let segments = if let Type::Path(TypePath {
@@ -274,10 +463,7 @@ fn is_type<'a>(ty: &'a Type, ty_str: &str) -> Option<&'a Type> {
         return None;
     };
 
-    let seg = match segments.last() {
-        Some(seg) => seg,
-        None => return None,
-    };
+    let seg = segments.last()?;
 
     if seg.ident != ty_str {
         return None;
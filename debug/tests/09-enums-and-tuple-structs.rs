@@ -0,0 +1,53 @@
+// Enums and tuple or unit structs print the same way the standard Debug derive
+// prints them: tuple structs and variants through `debug_tuple`, variants with
+// named fields through `debug_struct` and unit variants as their bare name.
+//
+// Formats given with #[debug = "..."] apply to fields of any of them.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+pub struct Point(i32, #[debug = "0x{:x}"] u32);
+
+#[derive(CustomDebug)]
+pub struct Unit;
+
+#[derive(CustomDebug)]
+pub enum Shape<T> {
+    Empty,
+    Circle(T),
+    Rect {
+        width: T,
+        #[debug = "0b{:04b}"]
+        flags: u8,
+    },
+    Tagged(&'static str, Option<T>),
+}
+
+#[derive(CustomDebug)]
+pub enum Never {}
+
+fn assert_debug<F: std::fmt::Debug>() {}
+
+fn main() {
+    assert_eq!(format!("{:?}", Point(-1, 255)), "Point(-1, 0xff)");
+    assert_eq!(format!("{:?}", Unit), "Unit");
+
+    assert_eq!(format!("{:?}", Shape::<u8>::Empty), "Empty");
+    assert_eq!(format!("{:?}", Shape::Circle(3)), "Circle(3)");
+    assert_eq!(
+        format!("{:?}", Shape::Rect { width: 2, flags: 5 }),
+        "Rect { width: 2, flags: 0b0101 }",
+    );
+    assert_eq!(
+        format!("{:#?}", Shape::Circle(1.5)),
+        "Circle(\n    1.5,\n)",
+    );
+
+    assert_eq!(
+        format!("{:?}", Shape::Tagged("t", Some('x'))),
+        "Tagged(\"t\", Some('x'))",
+    );
+
+    assert_debug::<Never>();
+}
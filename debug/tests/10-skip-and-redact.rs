@@ -0,0 +1,108 @@
+// Fields holding secrets can be kept out of the output.
+//
+// #[debug(skip)] leaves a field out entirely and ends the output with `..` to
+// show that something is missing. #[debug(redact)] keeps the field but prints
+// `<redacted>` instead of its value, and #[debug(redact(len))] only reveals the
+// length of the value.
+//
+// #[debug(redact_all_except(...))] on the struct redacts every field except
+// the ones listed, so that fields added later are hidden by default.
+//
+// Fields that are skipped or redacted are never formatted, so their type
+// parameters do not need to implement Debug.
+
+use derive_debug::CustomDebug;
+
+#[derive(CustomDebug)]
+pub struct Login {
+    user: String,
+    #[debug(redact)]
+    password: String,
+    #[debug(redact(len))]
+    token: Vec<u8>,
+    #[debug(skip)]
+    session: u64,
+}
+
+#[derive(CustomDebug)]
+pub struct Secret(#[debug(skip)] String);
+
+#[derive(CustomDebug)]
+#[debug(redact_all_except(id, kind))]
+pub struct Account {
+    id: u32,
+    #[debug = "{:?}!"]
+    kind: &'static str,
+    iban: String,
+    #[debug(redact(len))]
+    holder: String,
+}
+
+#[derive(CustomDebug)]
+#[debug(redact_all_except(method))]
+pub enum Auth {
+    Anonymous,
+    Basic { method: &'static str, password: String },
+    Bearer(String),
+}
+
+pub struct Key;
+
+#[derive(CustomDebug)]
+pub struct Vault<K, S> {
+    name: &'static str,
+    #[debug(skip)]
+    key: K,
+    #[debug(redact(len))]
+    secrets: Vec<S>,
+}
+
+fn main() {
+    let login = Login {
+        user: "ferris".to_owned(),
+        password: "hunter2".to_owned(),
+        token: vec![0; 16],
+        session: 42,
+    };
+    assert_eq!(
+        format!("{:?}", login),
+        "Login { user: \"ferris\", password: <redacted>, token: <redacted, len 16>, .. }",
+    );
+
+    assert_eq!(format!("{:?}", Secret("s3cr3t".to_owned())), "Secret(..)");
+
+    let account = Account {
+        id: 7,
+        kind: "savings",
+        iban: "DE00 0000".to_owned(),
+        holder: "Ferris".to_owned(),
+    };
+    assert_eq!(
+        format!("{:?}", account),
+        "Account { id: 7, kind: \"savings\"!, iban: <redacted>, holder: <redacted, len 6> }",
+    );
+
+    assert_eq!(format!("{:?}", Auth::Anonymous), "Anonymous");
+    let basic = Auth::Basic {
+        method: "basic",
+        password: "hunter2".to_owned(),
+    };
+    assert_eq!(
+        format!("{:?}", basic),
+        "Basic { method: \"basic\", password: <redacted> }",
+    );
+    assert_eq!(
+        format!("{:?}", Auth::Bearer("token".to_owned())),
+        "Bearer(<redacted>)",
+    );
+
+    let vault = Vault {
+        name: "main",
+        key: Key,
+        secrets: vec![Key, Key],
+    };
+    assert_eq!(
+        format!("{:?}", vault),
+        "Vault { name: \"main\", secrets: <redacted, len 2>, .. }",
+    );
+}
@@ -9,4 +9,6 @@ fn tests() {
     t.pass("tests/06-bound-trouble.rs");
     t.pass("tests/07-associated-type.rs");
     t.pass("tests/08-escape-hatch.rs");
+    t.pass("tests/09-enums-and-tuple-structs.rs");
+    t.pass("tests/10-skip-and-redact.rs");
 }